@@ -0,0 +1,1149 @@
+use std::env;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use crate::config::{Compositor, Generator};
+
+static WORKER: OnceLock<Sender<Job>> = OnceLock::new();
+static QUIET: AtomicBool = AtomicBool::new(false);
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+// Keys pressed by `hold-` actions and not yet released, in press order
+static HELD: Mutex<Vec<u16>> = Mutex::new(Vec::new());
+// Set once no sound player was found, so that is only reported once
+static NO_PLAYER: AtomicBool = AtomicBool::new(false);
+
+// Suppresses desktop notifications for failed actions; they are still logged.
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+// Prints the commands actions would run instead of running them. Menu
+// generators still run, so generated submenus can be browsed too.
+pub fn set_dry_run(dry_run: bool) {
+    DRY_RUN.store(dry_run, Ordering::Relaxed);
+}
+
+fn dry_run(action: &str, cmd: &Command) -> bool {
+    let dry_run = DRY_RUN.load(Ordering::Relaxed);
+    if dry_run {
+        eprintln!("waydo: dry run: `{}` would run {:?}", action, cmd);
+    }
+    dry_run
+}
+
+fn report_failure(action: &str, detail: &str) {
+    eprintln!("waydo: `{}` failed: {}", action, detail);
+    if QUIET.load(Ordering::Relaxed) {
+        return;
+    }
+    // Not waited for: a notification daemon slow to answer must not hold up
+    // the action queue or a generator.
+    let child = Command::new("notify-send")
+        .args([
+            "--app-name=waydo",
+            &format!("waydo: {action} failed"),
+            detail,
+        ])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    if let Ok(mut child) = child {
+        thread::spawn(move || child.wait());
+    }
+}
+
+// Runs `cmd` to completion, reporting a failure to start or a non-zero exit
+// along with whatever it wrote to stderr.
+fn run_checked(action: &str, cmd: &mut Command) {
+    if dry_run(action, cmd) {
+        return;
+    }
+    log::debug!("`{}`: running {:?}", action, cmd);
+    match cmd.stdin(Stdio::null()).stdout(Stdio::null()).output() {
+        Ok(out) if out.status.success() => {}
+        Ok(out) => {
+            let stderr = String::from_utf8_lossy(&out.stderr);
+            let detail = match stderr.trim() {
+                "" => out.status.to_string(),
+                msg => msg.to_string(),
+            };
+            report_failure(action, &detail);
+        }
+        Err(e) => report_failure(action, &e.to_string()),
+    }
+}
+
+// A command plus what it needs to know to run.
+pub struct Job {
+    pub cmd: String,
+    pub compositor: Compositor,
+    // Pause between the combos of a `key-` sequence
+    pub key_delay: Duration,
+    // For the programs `shell-` and `spawn` start
+    pub cwd: Option<PathBuf>,
+    pub env: Vec<(String, String)>,
+}
+
+impl Job {
+    fn overrides_env(&self) -> bool {
+        self.cwd.is_some() || !self.env.is_empty()
+    }
+
+    fn configure(&self, cmd: &mut Command) {
+        if let Some(dir) = &self.cwd {
+            cmd.current_dir(dir);
+        }
+        cmd.envs(self.env.iter().map(|(k, v)| (k, v)));
+    }
+}
+
+// Commands run one at a time on a worker thread, so key sequences keep their
+// order and their inter-key sleeps never stall the GTK main loop.
+pub fn dispatch(job: Job) {
+    let tx = WORKER.get_or_init(|| {
+        let (tx, rx) = mpsc::channel::<Job>();
+        thread::spawn(move || {
+            for job in rx {
+                run_action(&job);
+            }
+        });
+        tx
+    });
+    let _ = tx.send(job);
+}
+
+// Each compositor exports its IPC socket to the clients it starts; the
+// desktop name is the fallback for sessions started some other way.
+pub fn detect_compositor() -> Compositor {
+    if env::var_os("SWAYSOCK").is_some() {
+        return Compositor::Sway;
+    }
+    if env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+        return Compositor::Hyprland;
+    }
+    if env::var_os("NIRI_SOCKET").is_some() {
+        return Compositor::Niri;
+    }
+    let desktop = env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .to_lowercase();
+    if desktop.contains("sway") {
+        Compositor::Sway
+    } else if desktop.contains("hyprland") {
+        Compositor::Hyprland
+    } else {
+        Compositor::Niri
+    }
+}
+
+// Connector name (e.g. "DP-1") of the output the compositor has focused.
+pub fn focused_output(compositor: Compositor) -> Option<String> {
+    let (args, list): (&[&str], bool) = match compositor {
+        Compositor::Niri => (&["msg", "--json", "focused-output"], false),
+        Compositor::Sway => (&["-t", "get_outputs", "-r"], true),
+        Compositor::Hyprland => (&["monitors", "-j"], true),
+    };
+    let json = compositor_json(compositor, args)?;
+    let output = if list {
+        json.as_array()?
+            .iter()
+            .find(|o| o["focused"].as_bool() == Some(true))?
+    } else {
+        &json
+    };
+    Some(output["name"].as_str()?.to_string())
+}
+
+// What a menu about to open needs from the compositor.
+#[derive(Debug, Default)]
+pub struct Focus {
+    pub output: Option<String>,
+    pub window: WindowContext,
+}
+
+// Asks the compositor what has focus on a thread of its own, so a slow or
+// stuck IPC can't hold up the GTK thread. Whoever waits on the answer gives
+// up on it after a while. The focused window is only asked about when
+// `window` is set.
+pub fn query_focus(compositor: Compositor, window: bool) -> mpsc::Receiver<Focus> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = tx.send(Focus {
+            output: focused_output(compositor),
+            window: if window {
+                focused_window(compositor)
+            } else {
+                WindowContext::default()
+            },
+        });
+    });
+    rx
+}
+
+// Runs a generator's command on a thread of its own, so a slow one doesn't
+// hold up the overlay, and sends back (label, command) for each entry of its
+// output. Failures are reported and yield no entries.
+pub fn generate(generator: &Generator) -> mpsc::Receiver<Vec<(String, String)>> {
+    let (tx, rx) = mpsc::channel();
+    let generator = generator.clone();
+    thread::spawn(move || {
+        let _ = tx.send(entries(&generator));
+    });
+    rx
+}
+
+fn entries(generator: &Generator) -> Vec<(String, String)> {
+    let out = match Command::new("sh")
+        .arg("-c")
+        .arg(&generator.cmd)
+        .stdin(Stdio::null())
+        .output()
+    {
+        Ok(out) => out,
+        Err(e) => {
+            report_failure(&generator.cmd, &e.to_string());
+            return Vec::new();
+        }
+    };
+    if !out.status.success() {
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        report_failure(&generator.cmd, stderr.trim());
+        return Vec::new();
+    }
+    let stdout = String::from_utf8_lossy(&out.stdout);
+
+    let Some(field) = &generator.label else {
+        return stdout
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| (line.to_string(), generator.action.replace("{}", line)))
+            .collect();
+    };
+
+    let entries = match serde_json::from_str::<serde_json::Value>(&stdout) {
+        Ok(serde_json::Value::Array(entries)) => entries,
+        _ => {
+            report_failure(&generator.cmd, "output is not a JSON array");
+            return Vec::new();
+        }
+    };
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let label = json_text(&entry[field.as_str()])?;
+            Some((label, fill_template(&generator.action, entry)))
+        })
+        .collect()
+}
+
+fn json_text(v: &serde_json::Value) -> Option<String> {
+    match v {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Null => None,
+        v => Some(v.to_string()),
+    }
+}
+
+// Replaces each `{field}` in `template` with that field of `entry`. Unknown
+// fields are left as written.
+fn fill_template(template: &str, entry: &serde_json::Value) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        match after.find('}') {
+            Some(close) => {
+                let name = &after[..close];
+                match json_text(&entry[name]) {
+                    Some(value) => out.push_str(&value),
+                    None => out.push_str(&rest[open..open + close + 2]),
+                }
+                rest = &after[close + 1..];
+            }
+            None => {
+                out.push_str(&rest[open..]);
+                rest = "";
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+// What the compositor reports about the focused window.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WindowContext {
+    pub focused: bool,
+    pub floating: bool,
+    pub fullscreen: bool,
+}
+
+fn compositor_json(compositor: Compositor, args: &[&str]) -> Option<serde_json::Value> {
+    let out = Command::new(compositor_tool(compositor))
+        .args(args)
+        .output()
+        .ok()?;
+    serde_json::from_slice(&out.stdout).ok()
+}
+
+// Queries the focused window; any failure reads as "no window focused".
+pub fn focused_window(compositor: Compositor) -> WindowContext {
+    fn sway_focused(node: &serde_json::Value) -> Option<&serde_json::Value> {
+        if node["focused"].as_bool() == Some(true) {
+            return Some(node);
+        }
+        ["nodes", "floating_nodes"]
+            .iter()
+            .filter_map(|key| node[key].as_array())
+            .flatten()
+            .find_map(sway_focused)
+    }
+
+    let window = match compositor {
+        Compositor::Niri => compositor_json(compositor, &["msg", "--json", "focused-window"])
+            .filter(|w| w.is_object())
+            .map(|w| WindowContext {
+                focused: true,
+                floating: w["is_floating"].as_bool().unwrap_or(false),
+                // niri's IPC has no fullscreen state, so conditions on it
+                // are refused when the config is loaded.
+                fullscreen: false,
+            }),
+        Compositor::Sway => {
+            compositor_json(compositor, &["-t", "get_tree", "-r"]).and_then(|tree| {
+                let node = sway_focused(&tree)?;
+                let kind = node["type"].as_str()?;
+                (kind == "con" || kind == "floating_con").then(|| WindowContext {
+                    focused: true,
+                    floating: kind == "floating_con",
+                    fullscreen: node["fullscreen_mode"].as_i64().unwrap_or(0) != 0,
+                })
+            })
+        }
+        // Without a focused window Hyprland answers with an empty object.
+        Compositor::Hyprland => compositor_json(compositor, &["activewindow", "-j"])
+            .filter(|w| w.get("address").is_some())
+            .map(|w| WindowContext {
+                focused: true,
+                floating: w["floating"].as_bool().unwrap_or(false),
+                fullscreen: match &w["fullscreen"] {
+                    serde_json::Value::Bool(b) => *b,
+                    v => v.as_i64().unwrap_or(0) != 0,
+                },
+            }),
+    };
+    window.unwrap_or_default()
+}
+
+fn compositor_tool(compositor: Compositor) -> &'static str {
+    match compositor {
+        Compositor::Niri => "niri",
+        Compositor::Sway => "swaymsg",
+        Compositor::Hyprland => "hyprctl",
+    }
+}
+
+fn in_path(name: &str) -> bool {
+    let Some(path) = env::var_os("PATH") else {
+        return false;
+    };
+    env::split_paths(&path).any(|dir| {
+        dir.join(name)
+            .metadata()
+            .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+    })
+}
+
+// Plays a sound file with the first player installed, on its own thread so
+// the menu never waits for it.
+pub fn play_sound(path: &Path) {
+    if NO_PLAYER.load(Ordering::Relaxed) {
+        return;
+    }
+    let players: [(&str, &[&str]); 3] = [
+        ("paplay", &[]),
+        ("pw-play", &[]),
+        ("canberra-gtk-play", &["-f"]),
+    ];
+    let Some((player, args)) = players.into_iter().find(|(name, _)| in_path(name)) else {
+        if !NO_PLAYER.swap(true, Ordering::Relaxed) {
+            eprintln!(
+                "waydo: warning: no paplay, pw-play or canberra-gtk-play found: sounds are off"
+            );
+        }
+        return;
+    };
+    let mut cmd = Command::new(player);
+    cmd.args(args)
+        .arg(path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    thread::spawn(move || match cmd.status() {
+        Ok(status) if !status.success() => log::debug!("{:?}: {}", cmd, status),
+        Ok(_) => {}
+        Err(e) => log::debug!("{:?}: {}", cmd, e),
+    });
+}
+
+// External tools the actions shell out to, paired with what stops working
+// without them.
+pub fn missing_tools(compositor: Compositor) -> Vec<String> {
+    [
+        ("ydotool", "key/type actions"),
+        (compositor_tool(compositor), "compositor actions"),
+    ]
+    .into_iter()
+    .filter(|(name, _)| !in_path(name))
+    .map(|(name, what)| format!("{name} not found: {what} will do nothing"))
+    .collect()
+}
+
+fn key_token_to_evdev(tok: &str) -> Option<u16> {
+    match tok {
+        "ctrl" => Some(29),
+        "shift" => Some(42),
+        "alt" => Some(56),
+        "meta" | "super" => Some(125),
+        "1" => Some(2),
+        "2" => Some(3),
+        "3" => Some(4),
+        "4" => Some(5),
+        "5" => Some(6),
+        "6" => Some(7),
+        "7" => Some(8),
+        "8" => Some(9),
+        "9" => Some(10),
+        "0" => Some(11),
+        "f1" => Some(59),
+        "f2" => Some(60),
+        "f3" => Some(61),
+        "f4" => Some(62),
+        "f5" => Some(63),
+        "f6" => Some(64),
+        "f7" => Some(65),
+        "f8" => Some(66),
+        "f9" => Some(67),
+        "f10" => Some(68),
+        "f11" => Some(87),
+        "f12" => Some(88),
+        "a" => Some(30),
+        "b" => Some(48),
+        "c" => Some(46),
+        "d" => Some(32),
+        "e" => Some(18),
+        "f" => Some(33),
+        "g" => Some(34),
+        "h" => Some(35),
+        "i" => Some(23),
+        "j" => Some(36),
+        "k" => Some(37),
+        "l" => Some(38),
+        "m" => Some(50),
+        "n" => Some(49),
+        "o" => Some(24),
+        "p" => Some(25),
+        "q" => Some(16),
+        "r" => Some(19),
+        "s" => Some(31),
+        "t" => Some(20),
+        "u" => Some(22),
+        "v" => Some(47),
+        "w" => Some(17),
+        "x" => Some(45),
+        "y" => Some(21),
+        "z" => Some(44),
+        "minus" | "-" => Some(12),
+        "equal" | "plus" | "+" => Some(13),
+        "delete" | "backspace" => Some(14),
+        "pageup" => Some(104),
+        "pagedown" => Some(109),
+        "tab" => Some(15),
+        "enter" | "return" => Some(28),
+        "esc" | "escape" => Some(1),
+        "space" => Some(57),
+        "up" => Some(103),
+        "down" => Some(108),
+        "left" => Some(105),
+        "right" => Some(106),
+        "home" => Some(102),
+        "end" => Some(107),
+        _ => None,
+    }
+}
+
+// ydotool's button numbering for `ydotool click`.
+fn button_code(name: &str) -> Option<u8> {
+    match name {
+        "left" => Some(0),
+        "right" => Some(1),
+        "middle" => Some(2),
+        "side" | "back" => Some(3),
+        "extra" | "forward" => Some(4),
+        _ => None,
+    }
+}
+
+// `click-middle`: press and release a mouse button.
+fn run_ydotool_click(button: &str) {
+    let Some(code) = button_code(button) else {
+        eprintln!("waydo: unknown mouse button `{}`", button);
+        return;
+    };
+    // 0x40 is the down event and 0x80 the up event.
+    let arg = format!("0x{:02X}", 0xC0 | code);
+    run_checked(
+        &format!("click-{button}"),
+        Command::new("ydotool").args(["click", &arg]),
+    );
+}
+
+// `scroll-up 3`: turn the wheel some notches (1 if no count is given).
+fn run_ydotool_scroll(spec: &str) {
+    let (x, y) = match parse_scroll(spec) {
+        Ok(wheel) => wheel,
+        Err(e) => {
+            eprintln!("waydo: {}", e);
+            return;
+        }
+    };
+    let (x, y) = (x.to_string(), y.to_string());
+    run_checked(
+        &format!("scroll-{spec}"),
+        Command::new("ydotool").args(["mousemove", "--wheel", "-x", &x, "-y", &y]),
+    );
+}
+
+// Releases every key still held by `hold-` actions, so closing the menu
+// never leaves a modifier stuck down. Called on the GTK thread: ydotool is
+// started but not waited for.
+pub fn release_held() {
+    let codes = take_held();
+    if !codes.is_empty() {
+        spawn_detached("release-all", &mut ydotool_keys(&codes, false));
+    }
+}
+
+// Same, but waited for, for when the process is about to exit.
+pub fn release_all() {
+    run_ydotool_hold("all", false);
+}
+
+// `hold-ctrl-shift` presses keys and leaves them down; `release-...` lets
+// them go, and `release-all` releases everything still held. `HELD` is only
+// locked while it is updated, never while ydotool runs.
+fn run_ydotool_hold(spec: &str, down: bool) {
+    let codes = if !down && spec == "all" {
+        take_held()
+    } else {
+        let codes = match parse_combo(spec) {
+            Ok(codes) => codes,
+            Err(e) => {
+                eprintln!("waydo: {}", e);
+                return;
+            }
+        };
+        let mut held = HELD.lock().unwrap();
+        for code in &codes {
+            held.retain(|c| c != code);
+            if down {
+                held.push(*code);
+            }
+        }
+        codes
+    };
+    if codes.is_empty() {
+        return;
+    }
+    let action = format!("{}-{spec}", if down { "hold" } else { "release" });
+    run_checked(&action, &mut ydotool_keys(&codes, down));
+}
+
+// Everything held so far, last pressed first, leaving nothing held.
+fn take_held() -> Vec<u16> {
+    let mut codes = std::mem::take(&mut *HELD.lock().unwrap());
+    codes.reverse();
+    codes
+}
+
+fn ydotool_keys(codes: &[u16], down: bool) -> Command {
+    let state = if down { 1 } else { 0 };
+    let mut cmd = Command::new("ydotool");
+    cmd.arg("key")
+        .args(codes.iter().map(|code| format!("{code}:{state}")));
+    cmd
+}
+
+// Wheel steps for `scroll-<direction> [count]`, on the axes as the kernel
+// reports them: positive is up, or right.
+fn parse_scroll(spec: &str) -> Result<(i32, i32), String> {
+    let mut parts = spec.split_whitespace();
+    let direction = parts.next().unwrap_or_default();
+    let count = match parts.next().map(str::parse::<i32>) {
+        None => 1,
+        Some(Ok(n)) if n > 0 && parts.next().is_none() => n,
+        _ => return Err(format!("`scroll-{spec}` needs a positive count")),
+    };
+    match direction {
+        "up" => Ok((0, count)),
+        "down" => Ok((0, -count)),
+        "right" => Ok((count, 0)),
+        "left" => Ok((-count, 0)),
+        _ => Err(format!("unknown scroll direction `{direction}`")),
+    }
+}
+
+// What would keep `action` from running as written, found without running
+// anything. Compositor actions are passed through, so only their program is
+// known to exist or not.
+pub fn check(action: &str) -> Result<(), String> {
+    if action.trim().is_empty() {
+        return Err("empty command".to_string());
+    }
+    if action == "type-" {
+        return Err("`type-` has no text to type".to_string());
+    }
+    if action == "copy-" {
+        return Err("`copy-` has no text to copy".to_string());
+    }
+    if let Some(spec) = action.strip_prefix("key-") {
+        if spec.trim().is_empty() {
+            return Err("`key-` has no keys".to_string());
+        }
+        return spec
+            .split_whitespace()
+            .try_for_each(|combo| parse_combo(combo).map(drop));
+    }
+    if let Some(button) = action.strip_prefix("click-") {
+        return button_code(button.trim())
+            .map(drop)
+            .ok_or_else(|| format!("unknown mouse button `{}`", button.trim()));
+    }
+    if let Some(spec) = action.strip_prefix("scroll-") {
+        return parse_scroll(spec).map(drop);
+    }
+    if let Some(spec) = action
+        .strip_prefix("hold-")
+        .or_else(|| action.strip_prefix("release-").filter(|&s| s != "all"))
+    {
+        if parse_combo(spec)?.is_empty() {
+            return Err(format!("`{action}` has no keys"));
+        }
+        return Ok(());
+    }
+    if action
+        .strip_prefix("shell-")
+        .is_some_and(|s| s.trim().is_empty())
+    {
+        return Err("`shell-` has no command".to_string());
+    }
+    Ok(())
+}
+
+fn run_ydotool_sequence(spec: &str, delay: Duration) {
+    for combo in spec.split_whitespace() {
+        run_ydotool_combo(combo);
+        // Small spacing helps tools/apps register successive synthetic keys reliably.
+        if !delay.is_zero() {
+            thread::sleep(delay);
+        }
+    }
+}
+
+// Splits a chord into its keys. They are joined with `+` (`ctrl+minus`) or,
+// when there is no `+` but possibly a final one, with `-` (`ctrl-shift-z`).
+// A doubled separator at the end is the key itself, so `ctrl--` and
+// `ctrl+-` both press ctrl and minus, and a lone `-` is minus.
+fn combo_keys(spec: &str) -> Vec<&str> {
+    let body = spec.char_indices().last().map_or("", |(i, _)| &spec[..i]);
+    let sep = if body.contains('+') { '+' } else { '-' };
+    if let Some(head) = spec.strip_suffix(sep) {
+        if head.is_empty() {
+            return vec![spec];
+        }
+        if let Some(head) = head.strip_suffix(sep) {
+            let mut keys: Vec<&str> = head.split(sep).collect();
+            keys.push(&spec[spec.len() - sep.len_utf8()..]);
+            return keys;
+        }
+    }
+    spec.split(sep).collect()
+}
+
+// Evdev codes of a chord's keys, modifiers first.
+fn parse_combo(spec: &str) -> Result<Vec<u16>, String> {
+    combo_keys(spec)
+        .into_iter()
+        .map(|key| {
+            key_token_to_evdev(key).ok_or_else(|| format!("unknown key `{key}` in `{spec}`"))
+        })
+        .collect()
+}
+
+fn run_ydotool_combo(spec: &str) {
+    let codes = match parse_combo(spec) {
+        Ok(codes) if !codes.is_empty() => codes,
+        Ok(_) => return,
+        Err(e) => {
+            eprintln!("waydo: {}", e);
+            return;
+        }
+    };
+
+    // Press everything in order, then release in reverse.
+    let mut args: Vec<String> = vec!["key".to_string()];
+    args.extend(codes.iter().map(|code| format!("{code}:1")));
+    args.extend(codes.iter().rev().map(|code| format!("{code}:0")));
+
+    run_checked(&format!("key-{spec}"), Command::new("ydotool").args(&args));
+}
+
+// Expands a leading `~` to $HOME and `$VAR` / `${VAR}` anywhere in `token`;
+// unset variables expand to nothing. `\$` gives a literal `$`. `shell-`
+// commands don't need this, sh does the same expansion itself.
+fn expand(token: &str) -> String {
+    let mut out = String::with_capacity(token.len());
+    let mut rest = token;
+    if let Some(tail) = rest.strip_prefix('~')
+        && (tail.is_empty() || tail.starts_with('/'))
+        && let Some(home) = env::var_os("HOME")
+    {
+        out.push_str(&home.to_string_lossy());
+        rest = tail;
+    }
+
+    let mut chars = rest.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'$') => {
+                out.push('$');
+                chars.next();
+            }
+            '$' => {
+                let braced = chars.next_if_eq(&'{').is_some();
+                let mut name = String::new();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                    name.push(c);
+                }
+                if name.is_empty() || (braced && chars.next_if_eq(&'}').is_none()) {
+                    // Not a variable reference; keep it as written.
+                    out.push('$');
+                    if braced {
+                        out.push('{');
+                    }
+                    out.push_str(&name);
+                    continue;
+                }
+                if let Some(value) = env::var_os(&name) {
+                    out.push_str(&value.to_string_lossy());
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// niri action names with a direct equivalent elsewhere, as
+// (niri, sway command, Hyprland dispatcher).
+const ACTION_NAMES: &[(&str, &str, &str)] = &[
+    ("close-window", "kill", "killactive"),
+    ("fullscreen-window", "fullscreen toggle", "fullscreen 0"),
+    (
+        "maximize-window-to-edges",
+        "fullscreen toggle",
+        "fullscreen 1",
+    ),
+    (
+        "toggle-window-floating",
+        "floating toggle",
+        "togglefloating",
+    ),
+    (
+        "switch-focus-between-floating-and-tiling",
+        "focus mode_toggle",
+        "cyclenext floating",
+    ),
+    ("focus-column-left", "focus left", "movefocus l"),
+    ("focus-column-right", "focus right", "movefocus r"),
+    ("focus-workspace-up", "workspace prev", "workspace e-1"),
+    ("focus-workspace-down", "workspace next", "workspace e+1"),
+    (
+        "move-window-to-workspace-up",
+        "move container to workspace prev",
+        "movetoworkspace e-1",
+    ),
+    (
+        "move-window-to-workspace-down",
+        "move container to workspace next",
+        "movetoworkspace e+1",
+    ),
+    ("swap-window-left", "move left", "swapwindow l"),
+    ("swap-window-right", "move right", "swapwindow r"),
+];
+
+// niri's actions as of 25.08, for `niri_actions` when niri isn't around to
+// ask.
+pub const NIRI_ACTIONS: &[&str] = &[
+    "quit",
+    "power-off-monitors",
+    "power-on-monitors",
+    "spawn",
+    "spawn-sh",
+    "do-screen-transition",
+    "screenshot",
+    "screenshot-screen",
+    "screenshot-window",
+    "toggle-keyboard-shortcuts-inhibit",
+    "close-window",
+    "fullscreen-window",
+    "toggle-windowed-fullscreen",
+    "focus-window",
+    "focus-window-in-column",
+    "focus-window-previous",
+    "focus-column-left",
+    "focus-column-right",
+    "focus-column-first",
+    "focus-column-last",
+    "focus-column-right-or-first",
+    "focus-column-left-or-last",
+    "focus-column",
+    "focus-window-or-monitor-up",
+    "focus-window-or-monitor-down",
+    "focus-column-or-monitor-left",
+    "focus-column-or-monitor-right",
+    "focus-window-down",
+    "focus-window-up",
+    "focus-window-down-or-column-left",
+    "focus-window-down-or-column-right",
+    "focus-window-up-or-column-left",
+    "focus-window-up-or-column-right",
+    "focus-window-or-workspace-down",
+    "focus-window-or-workspace-up",
+    "focus-window-top",
+    "focus-window-bottom",
+    "focus-window-down-or-top",
+    "focus-window-up-or-bottom",
+    "move-column-left",
+    "move-column-right",
+    "move-column-to-first",
+    "move-column-to-last",
+    "move-column-left-or-to-monitor-left",
+    "move-column-right-or-to-monitor-right",
+    "move-column-to-index",
+    "move-window-down",
+    "move-window-up",
+    "move-window-down-or-to-workspace-down",
+    "move-window-up-or-to-workspace-up",
+    "consume-or-expel-window-left",
+    "consume-or-expel-window-right",
+    "consume-window-into-column",
+    "expel-window-from-column",
+    "swap-window-left",
+    "swap-window-right",
+    "toggle-column-tabbed-display",
+    "set-column-display",
+    "center-column",
+    "center-window",
+    "center-visible-columns",
+    "focus-workspace-down",
+    "focus-workspace-up",
+    "focus-workspace",
+    "focus-workspace-previous",
+    "move-window-to-workspace-down",
+    "move-window-to-workspace-up",
+    "move-window-to-workspace",
+    "move-column-to-workspace-down",
+    "move-column-to-workspace-up",
+    "move-column-to-workspace",
+    "move-workspace-down",
+    "move-workspace-up",
+    "move-workspace-to-index",
+    "set-workspace-name",
+    "unset-workspace-name",
+    "focus-monitor-left",
+    "focus-monitor-right",
+    "focus-monitor-down",
+    "focus-monitor-up",
+    "focus-monitor-previous",
+    "focus-monitor-next",
+    "focus-monitor",
+    "move-window-to-monitor-left",
+    "move-window-to-monitor-right",
+    "move-window-to-monitor-down",
+    "move-window-to-monitor-up",
+    "move-window-to-monitor-previous",
+    "move-window-to-monitor-next",
+    "move-window-to-monitor",
+    "move-column-to-monitor-left",
+    "move-column-to-monitor-right",
+    "move-column-to-monitor-down",
+    "move-column-to-monitor-up",
+    "move-column-to-monitor-previous",
+    "move-column-to-monitor-next",
+    "move-column-to-monitor",
+    "set-window-width",
+    "set-window-height",
+    "reset-window-height",
+    "switch-preset-column-width",
+    "switch-preset-window-width",
+    "switch-preset-window-height",
+    "maximize-column",
+    "maximize-window-to-edges",
+    "set-column-width",
+    "expand-column-to-available-width",
+    "switch-layout",
+    "show-hotkey-overlay",
+    "move-workspace-to-monitor-left",
+    "move-workspace-to-monitor-right",
+    "move-workspace-to-monitor-down",
+    "move-workspace-to-monitor-up",
+    "move-workspace-to-monitor-previous",
+    "move-workspace-to-monitor-next",
+    "move-workspace-to-monitor",
+    "toggle-debug-tint",
+    "debug-toggle-opaque-regions",
+    "debug-toggle-damage",
+    "toggle-window-floating",
+    "move-window-to-floating",
+    "move-window-to-tiling",
+    "focus-floating",
+    "focus-tiling",
+    "switch-focus-between-floating-and-tiling",
+    "move-floating-window",
+    "toggle-window-rule-opacity",
+    "set-dynamic-cast-window",
+    "set-dynamic-cast-monitor",
+    "clear-dynamic-cast-target",
+    "toggle-overview",
+    "open-overview",
+    "close-overview",
+    "toggle-window-urgent",
+    "set-window-urgent",
+    "unset-window-urgent",
+];
+
+// Names of the actions the installed niri accepts, read from its help, or
+// None without a working niri.
+pub fn niri_actions() -> Option<Vec<String>> {
+    let out = Command::new("niri")
+        .args(["msg", "action", "--help"])
+        .output()
+        .ok()
+        .filter(|out| out.status.success())?;
+    let help = String::from_utf8_lossy(&out.stdout);
+    // clap lists each subcommand under `Commands:` indented by two spaces;
+    // wrapped descriptions are indented further.
+    let names: Vec<String> = help
+        .lines()
+        .skip_while(|line| line.trim() != "Commands:")
+        .skip(1)
+        .take_while(|line| !line.trim().is_empty())
+        .filter(|line| line.starts_with("  ") && !line[2..].starts_with(' '))
+        .filter_map(|line| line.split_whitespace().next())
+        .filter(|&name| name != "help")
+        .map(str::to_string)
+        .collect();
+    (!names.is_empty()).then_some(names)
+}
+
+// The compositor's own spelling of a niri-style `action`. Unknown actions
+// are assumed to already be native to the compositor.
+fn translate(compositor: Compositor, action: &str) -> String {
+    if compositor == Compositor::Niri {
+        return action.to_string();
+    }
+    if let Some(program) = action.strip_prefix("spawn -- ") {
+        return format!("exec {program}");
+    }
+    let native = ACTION_NAMES
+        .iter()
+        .find(|(niri, _, _)| *niri == action.trim())
+        .map(|(_, sway, hyprland)| match compositor {
+            Compositor::Sway => *sway,
+            _ => *hyprland,
+        });
+    native.unwrap_or(action).to_string()
+}
+
+// Puts `text` on the clipboard exactly as written, spaces and all. wl-copy
+// stays behind to serve it; only the process that forks it is waited for.
+fn run_wl_copy(action: &str, text: &str) {
+    if !in_path("wl-copy") {
+        report_failure(action, "wl-copy not found; install wl-clipboard");
+        return;
+    }
+    let mut cmd = Command::new("wl-copy");
+    if dry_run(action, &cmd) {
+        return;
+    }
+    log::debug!("`{}`: copying {} bytes", action, text.len());
+    cmd.stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    let copied = cmd.spawn().and_then(|mut child| {
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        child.wait()
+    });
+    match copied {
+        Ok(status) if !status.success() => report_failure(action, &status.to_string()),
+        Ok(_) => {}
+        Err(e) => report_failure(action, &e.to_string()),
+    }
+}
+
+// Not waited for here: a long-running program must not hold up the queue.
+fn spawn_detached(action: &str, cmd: &mut Command) {
+    if dry_run(action, cmd) {
+        return;
+    }
+    log::debug!("`{}`: running {:?}", action, cmd);
+    match cmd.spawn() {
+        Ok(mut child) => {
+            let action = action.to_string();
+            thread::spawn(move || match child.wait() {
+                Ok(status) if !status.success() => report_failure(&action, &status.to_string()),
+                Ok(_) => {}
+                Err(e) => report_failure(&action, &e.to_string()),
+            });
+        }
+        Err(e) => report_failure(action, &e.to_string()),
+    }
+}
+
+// The program and arguments of a `spawn [--] program args...` action, split
+// and expanded the way they would be passed to the compositor.
+fn spawn_argv(action: &str) -> Option<Vec<String>> {
+    let rest = action.strip_prefix("spawn")?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let rest = rest.trim_start();
+    let rest = rest.strip_prefix("--").unwrap_or(rest);
+    let argv: Vec<String> = rest.split_whitespace().map(expand).collect();
+    (!argv.is_empty()).then_some(argv)
+}
+
+fn run_action(job: &Job) {
+    let (action, compositor) = (job.cmd.as_str(), job.compositor);
+
+    if let Some(text) = action.strip_prefix("type-") {
+        if !text.is_empty() {
+            run_checked(action, Command::new("ydotool").args(["type", "--", text]));
+        }
+        return;
+    }
+
+    if let Some(spec) = action.strip_prefix("key-") {
+        run_ydotool_sequence(spec, job.key_delay);
+        return;
+    }
+
+    if let Some(text) = action.strip_prefix("copy-") {
+        run_wl_copy(action, text);
+        return;
+    }
+
+    if let Some(button) = action.strip_prefix("click-") {
+        run_ydotool_click(button.trim());
+        return;
+    }
+
+    if let Some(spec) = action.strip_prefix("scroll-") {
+        run_ydotool_scroll(spec);
+        return;
+    }
+
+    if let Some(spec) = action.strip_prefix("hold-") {
+        run_ydotool_hold(spec, true);
+        return;
+    }
+
+    if let Some(spec) = action.strip_prefix("release-") {
+        run_ydotool_hold(spec, false);
+        return;
+    }
+
+    // Handed to sh verbatim, so quoting, pipes and $(...) work as typed.
+    if let Some(script) = action.strip_prefix("shell-") {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(script);
+        job.configure(&mut cmd);
+        spawn_detached(action, &mut cmd);
+        return;
+    }
+
+    // The compositor would start the program in its own directory and
+    // environment, so one that needs others is started here instead.
+    if job.overrides_env()
+        && let Some(argv) = spawn_argv(action)
+    {
+        let mut cmd = Command::new(&argv[0]);
+        cmd.args(&argv[1..]);
+        job.configure(&mut cmd);
+        spawn_detached(action, &mut cmd);
+        return;
+    }
+
+    let mut cmd = Command::new(compositor_tool(compositor));
+    match compositor {
+        Compositor::Niri => cmd.args(["msg", "action"]),
+        Compositor::Sway => &mut cmd,
+        Compositor::Hyprland => cmd.arg("dispatch"),
+    };
+    for part in translate(compositor, action).split_whitespace() {
+        cmd.arg(expand(part));
+    }
+    run_checked(action, &mut cmd);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chords_split_on_dashes() {
+        assert_eq!(parse_combo("ctrl-shift-z"), Ok(vec![29, 42, 44]));
+        assert_eq!(parse_combo("ctrl-minus"), Ok(vec![29, 12]));
+        assert_eq!(parse_combo("minus"), Ok(vec![12]));
+    }
+
+    #[test]
+    fn chords_ending_in_a_non_ascii_key_are_split_on_char_boundaries() {
+        assert_eq!(combo_keys("ctrl-é"), ["ctrl", "é"]);
+        assert_eq!(combo_keys("é"), ["é"]);
+        assert!(parse_combo("ctrl-é").unwrap_err().contains("`é`"));
+        assert!(check("key-ctrl-é").is_err());
+    }
+
+    #[test]
+    fn chords_can_use_plus_and_a_literal_minus() {
+        assert_eq!(parse_combo("ctrl+shift+z"), Ok(vec![29, 42, 44]));
+        assert_eq!(parse_combo("ctrl+-"), Ok(vec![29, 12]));
+        assert_eq!(parse_combo("ctrl--"), Ok(vec![29, 12]));
+        assert_eq!(parse_combo("ctrl-+"), Ok(vec![29, 13]));
+        assert_eq!(parse_combo("ctrl++"), Ok(vec![29, 13]));
+        assert_eq!(parse_combo("-"), Ok(vec![12]));
+    }
+
+    #[test]
+    fn stray_separators_are_reported() {
+        assert!(
+            parse_combo("ctrl--z")
+                .unwrap_err()
+                .contains("unknown key ``")
+        );
+        assert!(parse_combo("ctrl-").is_err());
+        assert!(parse_combo("ctrl-nope").unwrap_err().contains("`nope`"));
+    }
+}
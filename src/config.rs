@@ -0,0 +1,1522 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use toml::{Table, Value};
+
+#[derive(Clone, Debug)]
+pub struct Action {
+    pub cmd: String,
+    pub close_on_click: bool,
+    // Overrides `Settings::key_delay_ms` for this action's key sequence
+    pub key_delay_ms: Option<u64>,
+    // Overrides `Settings::sound` for this action
+    pub sound: Option<PathBuf>,
+    // Only runs when clicked a second time while armed by the first click
+    pub confirm: bool,
+    // Wait after the menu has hidden before running, so the overlay is off
+    // the screen first
+    pub delay_before_ms: u64,
+    // Working directory and extra environment for the programs the action
+    // starts; unset, they inherit the daemon's (or the compositor's)
+    pub cwd: Option<PathBuf>,
+    pub env: Vec<(String, String)>,
+}
+
+#[derive(Clone, Debug)]
+pub enum ItemKind {
+    Action(Action),
+    Submenu {
+        menu: Menu,
+        on_click: Option<Action>,
+        // Whether a click short of the ring runs `on_click` and closes
+        quick_click: bool,
+    },
+    // Empty slot that keeps its place in the layout but does nothing.
+    Blank,
+    // Submenu whose items come from a command's output when it is entered.
+    Generated(Generator),
+    // Replaces the whole menu with another named one instead of nesting it.
+    OpenMenu {
+        name: String,
+    },
+    // Each click moves on to the next of `actions` and runs it, in place;
+    // the item shows the label of the one last run, or its own before that.
+    Cycle {
+        actions: Vec<Action>,
+        labels: Vec<String>,
+    },
+}
+
+// Runs `cmd` with sh -c and turns each entry of its output into an item.
+// Without `label` every non-empty line is an entry, shown as is and
+// substituted for `{}` in `action`. With `label` the output is a JSON array
+// of objects: that field is shown and `{field}` in `action` is replaced by
+// the entry's value for it.
+#[derive(Clone, Debug)]
+pub struct Generator {
+    pub cmd: String,
+    pub label: Option<String>,
+    pub action: String,
+    pub close_on_click: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Menu {
+    // Key of the [menus.<name>] table it was built from
+    pub name: String,
+    pub items: Vec<MenuItem>,
+    // Overrides `Settings::layout` for this menu
+    pub layout: Option<MenuLayout>,
+    // Actions leave the menu open and submenus open in place, for clicking
+    // the same items repeatedly
+    pub sticky: bool,
+    // 0 as configured; n for the nth overflow page `max_items` split off
+    pub page: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Color {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+    pub a: f64,
+}
+
+impl Color {
+    // Relative luminance (Rec. 709 weights) of the color, ignoring alpha.
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
+    // Moved towards white by `t` (0..1), or towards black for negative `t`.
+    pub fn shade(&self, t: f64) -> Color {
+        let target = if t < 0.0 { 0.0 } else { 1.0 };
+        let t = t.abs();
+        let mix = |c: f64| c + (target - c) * t;
+        rgba(mix(self.r), mix(self.g), mix(self.b), self.a)
+    }
+}
+
+const fn rgba(r: f64, g: f64, b: f64, a: f64) -> Color {
+    Color { r, g, b, a }
+}
+
+// Colors for everything items don't set themselves.
+#[derive(Clone, Debug)]
+pub struct Theme {
+    pub item: Color,
+    pub submenu: Color,
+    // Center circle of the root menu and of submenus
+    pub center: Color,
+    pub center_submenu: Color,
+    // Outline of items and the center
+    pub stroke: Color,
+    pub stroke_width: f64,
+    // Outline of the highlighted item
+    pub highlight: Color,
+    // Label color; picked from each fill's luminance when unset
+    pub text: Option<Color>,
+    // The X closing the root menu and the arrow going back from submenus,
+    // or icon theme names drawn instead
+    pub glyph: Color,
+    pub close_icon: Option<String>,
+    pub back_icon: Option<String>,
+    // Dot marking where the menu was opened while a submenu is shown, which
+    // also closes it when clicked; a radius of 0 hides it
+    pub anchor: Color,
+    pub anchor_radius: f64,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            item: rgba(0.15, 0.15, 0.15, 0.80),
+            submenu: rgba(0.31, 0.26, 0.40, 0.90),
+            center: rgba(0.75, 0.2, 0.2, 0.88),
+            center_submenu: rgba(0.22, 0.48, 0.82, 0.92),
+            stroke: rgba(1.0, 1.0, 1.0, 0.70),
+            stroke_width: 2.0,
+            highlight: rgba(1.0, 1.0, 1.0, 1.0),
+            text: None,
+            glyph: rgba(1.0, 1.0, 1.0, 0.95),
+            close_icon: None,
+            back_icon: None,
+            anchor: rgba(1.0, 1.0, 1.0, 0.22),
+            anchor_radius: 10.0,
+        }
+    }
+}
+
+impl Theme {
+    pub fn light() -> Self {
+        Self {
+            item: rgba(0.96, 0.96, 0.96, 0.88),
+            submenu: rgba(0.83, 0.79, 0.93, 0.92),
+            center: rgba(0.85, 0.30, 0.30, 0.90),
+            center_submenu: rgba(0.30, 0.55, 0.88, 0.92),
+            stroke: rgba(0.0, 0.0, 0.0, 0.35),
+            stroke_width: 2.0,
+            highlight: rgba(0.0, 0.0, 0.0, 0.85),
+            text: None,
+            glyph: rgba(1.0, 1.0, 1.0, 0.95),
+            close_icon: None,
+            back_icon: None,
+            anchor: rgba(1.0, 1.0, 1.0, 0.22),
+            anchor_radius: 10.0,
+        }
+    }
+
+    // The item's own color, or the default for its kind.
+    pub fn fill(&self, item: &MenuItem) -> Color {
+        item.color.unwrap_or(match item.kind {
+            ItemKind::Submenu { .. } | ItemKind::Generated(_) | ItemKind::OpenMenu { .. } => {
+                self.submenu
+            }
+            ItemKind::Action(_) | ItemKind::Cycle { .. } | ItemKind::Blank => self.item,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MenuItem {
+    pub label: String,
+    pub kind: ItemKind,
+    // Fill; the theme's item or submenu color when unset
+    pub color: Option<Color>,
+    // Label color; picked from the fill's luminance when unset
+    pub text_color: Option<Color>,
+    // Label size before `scale`; the geometry's `font_size` when unset
+    pub font_size: Option<f64>,
+    // Icon theme name drawn instead of the label when it resolves
+    pub icon: Option<String>,
+    // Image file filling the item instead of its label or icon
+    pub image: Option<PathBuf>,
+    // Disabled items are shown faded and can't be selected
+    pub enabled: bool,
+    // Hidden unless this holds for the focused window when the menu opens
+    pub show_when: Option<Condition>,
+    // Shown as a tooltip while hovered, with `tooltips` on
+    pub description: Option<String>,
+    // Run instead of the item's own action or submenu when it is clicked
+    // with Shift or Ctrl held
+    pub shift_action: Option<Action>,
+    pub ctrl_action: Option<Action>,
+}
+
+// State of the focused window an item can depend on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WindowState {
+    // Any window has focus.
+    Focused,
+    Floating,
+    Tiling,
+    Fullscreen,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Condition {
+    pub state: WindowState,
+    // Written with a leading `!`
+    pub negate: bool,
+}
+
+impl MenuItem {
+    pub fn selectable(&self) -> bool {
+        self.enabled && !matches!(self.kind, ItemKind::Blank)
+    }
+}
+
+// Where the menu appears when shown.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Spawn {
+    // Wait for the first pointer motion or click.
+    #[default]
+    Motion,
+    // Where the pointer enters the surface once shown.
+    Pointer,
+    // In the middle of the output.
+    Center,
+}
+
+// How a pointer button picks an item.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Selection {
+    // Press and release on the item.
+    #[default]
+    Click,
+    // The press that anchors the menu is held; letting go over an item
+    // selects it.
+    ReleaseDrag,
+}
+
+// Which of the two palettes is drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ColorScheme {
+    #[default]
+    Dark,
+    Light,
+}
+
+// Backend that plain (non-`key-`/`type-`/`shell-`) actions are sent to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compositor {
+    Niri,
+    Sway,
+    Hyprland,
+}
+
+// How items are arranged around the center.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum MenuLayout {
+    // Separate circles on a ring.
+    #[default]
+    Ring,
+    // Annular wedges that together cover the whole disc.
+    Wedge,
+    // Rows of cells below the center, for menus too large for a ring.
+    Grid,
+}
+
+// What to do with labels wider than their item.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LabelOverflow {
+    // Break onto a second line, ellipsizing whatever still doesn't fit.
+    #[default]
+    Wrap,
+    // Keep one line and cut it off with an ellipsis.
+    Ellipsize,
+}
+
+// Menu dimensions in logical pixels, already multiplied by `scale`.
+#[derive(Clone, Copy, Debug)]
+pub struct Geometry {
+    pub center_radius: f64,
+    pub ring_distance: f64,
+    pub item_radius: f64,
+    pub font_size: f64,
+    // Minimum space between neighbouring item circles
+    pub item_gap: f64,
+    // Pointer distance from the center below which no item is selected
+    pub deadzone: f64,
+    // Margin past the outer edge of the ring beyond which nothing is selected
+    pub deadzone_outer: f64,
+    // What the sizes above were multiplied by, for item `font_size`s
+    pub scale: f64,
+}
+
+impl Default for Geometry {
+    fn default() -> Self {
+        Self {
+            center_radius: 18.0,
+            ring_distance: 86.0,
+            item_radius: 35.0,
+            font_size: 13.0,
+            item_gap: 8.0,
+            deadzone: 18.0,
+            deadzone_outer: 24.0,
+            scale: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Settings {
+    pub keyboard: bool,
+    pub spawn: Spawn,
+    pub selection: Selection,
+    // Reopen where the menu was last anchored instead of following `spawn`
+    pub reuse_position: bool,
+    pub layout: MenuLayout,
+    pub geometry: Geometry,
+    pub dark_theme: Theme,
+    pub light_theme: Theme,
+    // None follows the system's light/dark preference
+    pub theme: Option<ColorScheme>,
+    // Fade in/out duration; 0 shows and hides instantly
+    pub fade_ms: u64,
+    // Duration of the submenu enter/leave transition; 0 disables it
+    pub submenu_anim_ms: u64,
+    // Size of the hovered item relative to the others; 1 disables growing
+    pub hover_scale: f64,
+    // Duration of the grow/shrink when the hovered item changes
+    pub hover_ms: u64,
+    // Holding a click on the center this long closes the menu from any
+    // depth instead of going back one level; 0 disables
+    pub long_press_ms: u64,
+    // Hide a shown menu after this long without input; 0 never does
+    pub idle_hide_ms: u64,
+    // Toggles this soon after the last one acted on are ignored
+    pub toggle_debounce_ms: u64,
+    // Pause between the combos of a `key-` sequence; 0 sends them back to back
+    pub key_delay_ms: u64,
+    // Soft shadow under items and the center for contrast on light backgrounds
+    pub shadow: bool,
+    // Radial gradient fill derived from each item's color instead of a flat one
+    pub gradient: bool,
+    // Faint lines from the center to each item
+    pub spokes: bool,
+    // Shade the slice of the ring the highlighted item sits in
+    pub sector: bool,
+    // Shift a menu opened near an edge so all of it is on the output
+    pub keep_on_screen: bool,
+    // Degrees the ring turns per mouse wheel step; 0 leaves it still
+    pub wheel_step: f64,
+    // Menus with more items show this many per page, the last being a
+    // "More" item for the next page; 0 shows them all
+    pub max_items: usize,
+    // Played when an action runs, and when the pointer moves onto an item
+    pub sound: Option<PathBuf>,
+    pub hover_sound: Option<PathBuf>,
+    pub label_overflow: LabelOverflow,
+    // Only select items actually under the pointer; clicks elsewhere close
+    pub dismiss_outside: bool,
+    // Once anchored, take input only around the menu so other clicks reach
+    // the windows below
+    pub click_through: bool,
+    // Marking-menu style selection by flicking across the ring, no click needed
+    pub gesture: bool,
+    // Show what the hovered item does above the center
+    pub tooltips: bool,
+    // Number the first nine items for selecting them with the digit keys
+    pub show_shortcuts: bool,
+    // Fraction of the ring distance within which clicking a submenu item with
+    // an `on_click` runs it and closes instead of descending; 0 disables
+    pub quick_click_radius: f64,
+    // None detects the compositor from the environment
+    pub compositor: Option<Compositor>,
+    // Kept up to date with the `waydo status` line, for status bars
+    pub status_file: Option<PathBuf>,
+    // Move up to this many of each menu's most used items to its front; 0
+    // keeps the config order and records no usage
+    pub frequent: usize,
+    // Colors file read on top of [theme], and re-read whenever it changes
+    pub stylesheet: Option<PathBuf>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            keyboard: false,
+            spawn: Spawn::default(),
+            selection: Selection::default(),
+            reuse_position: false,
+            layout: MenuLayout::default(),
+            geometry: Geometry::default(),
+            dark_theme: Theme::default(),
+            light_theme: Theme::light(),
+            theme: None,
+            fade_ms: 120,
+            submenu_anim_ms: 100,
+            hover_scale: 1.15,
+            hover_ms: 80,
+            long_press_ms: 500,
+            idle_hide_ms: 0,
+            toggle_debounce_ms: 80,
+            key_delay_ms: 20,
+            shadow: true,
+            gradient: false,
+            spokes: false,
+            sector: false,
+            keep_on_screen: false,
+            wheel_step: 15.0,
+            max_items: 0,
+            sound: None,
+            hover_sound: None,
+            label_overflow: LabelOverflow::default(),
+            dismiss_outside: false,
+            click_through: false,
+            gesture: false,
+            tooltips: false,
+            show_shortcuts: false,
+            quick_click_radius: 0.6,
+            compositor: None,
+            status_file: None,
+            frequent: 0,
+            stylesheet: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    pub menus: HashMap<String, Menu>,
+    pub settings: Settings,
+}
+
+const DEFAULT_MENU: &str = include_str!("default_menu.toml");
+// Shown by `waydo toggle`; every other menu is opened by name.
+pub const ROOT_MENU: &str = "root";
+
+pub fn config_path() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("waydo").join("menu.toml"))
+}
+
+pub fn load_config() -> Result<Config, String> {
+    match config_path() {
+        Some(path) if path.exists() => load_file(&path),
+        _ => parse_config(DEFAULT_MENU),
+    }
+}
+
+fn load_file(path: &Path) -> Result<Config, String> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut doc = read_config(path)?;
+    let mut seen = vec![path.canonicalize().unwrap_or_else(|_| path.to_path_buf())];
+    include_menus(&mut doc, dir, &mut seen)?;
+    build_config(doc).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn read_config(path: &Path) -> Result<Table, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    text.parse()
+        .map_err(|e: toml::de::Error| format!("{}: {}", path.display(), e))
+}
+
+// Merges the [menus] of the files `doc` lists in `include`, and of the ones
+// those include, into its own. Paths are relative to the config directory.
+// `seen` holds the files already read, resolved, to catch a file included
+// twice, which is also what an include cycle amounts to.
+fn include_menus(doc: &mut Table, dir: &Path, seen: &mut Vec<PathBuf>) -> Result<(), String> {
+    let Some(includes) = doc.remove("include") else {
+        return Ok(());
+    };
+    let includes = includes
+        .as_array()
+        .and_then(|files| files.iter().map(Value::as_str).collect::<Option<Vec<_>>>())
+        .ok_or("`include` must be an array of file names")?;
+    for file in includes {
+        let path = dir.join(expand_home(file)?);
+        let Ok(resolved) = path.canonicalize() else {
+            return Err(format!("included file {} not found", path.display()));
+        };
+        if seen.contains(&resolved) {
+            return Err(format!(
+                "{} is included more than once (or includes itself)",
+                path.display()
+            ));
+        }
+        seen.push(resolved);
+        let mut included = read_config(&path)?;
+        include_menus(&mut included, dir, seen)?;
+        let menus = match included.remove("menus") {
+            Some(Value::Table(menus)) => menus,
+            Some(_) => return Err(format!("{}: `menus` must be a table", path.display())),
+            None => Table::new(),
+        };
+        if let Some(key) = included.keys().next() {
+            return Err(format!(
+                "{}: only [menus] and `include` can be in an included file, not `{key}`",
+                path.display()
+            ));
+        }
+        let own = doc
+            .entry("menus")
+            .or_insert_with(|| Value::Table(Table::new()))
+            .as_table_mut()
+            .ok_or("`menus` must be a table")?;
+        for (name, menu) in menus {
+            if own.contains_key(&name) {
+                return Err(format!(
+                    "{}: menu `{name}` is already defined",
+                    path.display()
+                ));
+            }
+            own.insert(name, menu);
+        }
+    }
+    Ok(())
+}
+
+pub fn parse_config(text: &str) -> Result<Config, String> {
+    build_config(text.parse().map_err(|e: toml::de::Error| e.to_string())?)
+}
+
+fn build_config(doc: Table) -> Result<Config, String> {
+    let menus = doc
+        .get("menus")
+        .and_then(Value::as_table)
+        .ok_or("missing [menus] table")?;
+    if !menus.contains_key(ROOT_MENU) {
+        return Err(format!("menu `{ROOT_MENU}` is not defined"));
+    }
+    let mut built = HashMap::with_capacity(menus.len());
+    for name in menus.keys() {
+        built.insert(name.clone(), build_menu(menus, name, &mut Vec::new())?);
+    }
+
+    let mut settings = match doc.get("settings") {
+        Some(v) => parse_settings(v.as_table().ok_or("`settings` must be a table")?)
+            .map_err(|e| format!("[settings]: {e}"))?,
+        None => Settings::default(),
+    };
+    if let Some(v) = doc.get("geometry") {
+        settings.geometry = parse_geometry(v.as_table().ok_or("`geometry` must be a table")?)
+            .map_err(|e| format!("[geometry]: {e}"))?;
+    }
+    if let Some(v) = doc.get("theme") {
+        // Keys directly under [theme] apply to both palettes, [theme.dark]
+        // and [theme.light] to one.
+        let t = v.as_table().ok_or("`theme` must be a table")?;
+        for (name, theme) in [
+            ("dark", &mut settings.dark_theme),
+            ("light", &mut settings.light_theme),
+        ] {
+            *theme = parse_theme(t, theme.clone()).map_err(|e| format!("[theme]: {e}"))?;
+            if let Some(v) = t.get(name) {
+                let sub = v
+                    .as_table()
+                    .ok_or_else(|| format!("`theme.{name}` must be a table"))?;
+                *theme =
+                    parse_theme(sub, theme.clone()).map_err(|e| format!("[theme.{name}]: {e}"))?;
+            }
+        }
+        if let Some(v) = t.get("stylesheet") {
+            let path = v.as_str().ok_or("[theme]: `stylesheet` must be a path")?;
+            let mut path = expand_home(path)?;
+            // Relative to the config file, like the config's other files.
+            if path.is_relative()
+                && let Some(dir) = config_path().as_deref().and_then(Path::parent)
+            {
+                path = dir.join(path);
+            }
+            let text = fs::read_to_string(&path)
+                .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+            for theme in [&mut settings.dark_theme, &mut settings.light_theme] {
+                *theme = parse_stylesheet(&text, theme.clone())
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+            }
+            settings.stylesheet = Some(path);
+        }
+    }
+
+    Ok(Config {
+        menus: built,
+        settings,
+    })
+}
+
+fn parse_settings(t: &Table) -> Result<Settings, String> {
+    let mut settings = Settings::default();
+    if let Some(v) = t.get("keyboard") {
+        settings.keyboard = v.as_bool().ok_or("`keyboard` must be a boolean")?;
+    }
+    if let Some(v) = t.get("shadow") {
+        settings.shadow = v.as_bool().ok_or("`shadow` must be a boolean")?;
+    }
+    if let Some(v) = t.get("gradient") {
+        settings.gradient = v.as_bool().ok_or("`gradient` must be a boolean")?;
+    }
+    if let Some(v) = t.get("keep_on_screen") {
+        settings.keep_on_screen = v.as_bool().ok_or("`keep_on_screen` must be a boolean")?;
+    }
+    if let Some(v) = t.get("sector") {
+        settings.sector = v.as_bool().ok_or("`sector` must be a boolean")?;
+    }
+    if let Some(v) = t.get("spokes") {
+        settings.spokes = v.as_bool().ok_or("`spokes` must be a boolean")?;
+    }
+    if let Some(v) = t.get("wheel_step") {
+        settings.wheel_step = match v {
+            Value::Float(f) => *f,
+            Value::Integer(i) => *i as f64,
+            _ => return Err("`wheel_step` must be a number of degrees".to_string()),
+        };
+    }
+    if let Some(v) = t.get("max_items") {
+        settings.max_items = v
+            .as_integer()
+            .and_then(|n| usize::try_from(n).ok())
+            .filter(|&n| n == 0 || n >= 2)
+            .ok_or("`max_items` must be 0 or at least 2")?;
+    }
+    if let Some(v) = t.get("frequent") {
+        settings.frequent = v
+            .as_integer()
+            .and_then(|n| usize::try_from(n).ok())
+            .ok_or("`frequent` must be a non-negative integer")?;
+    }
+    for (key, sound) in [
+        ("sound", &mut settings.sound),
+        ("hover_sound", &mut settings.hover_sound),
+    ] {
+        if let Some(v) = t.get(key) {
+            let path = v
+                .as_str()
+                .ok_or_else(|| format!("`{key}` must be a path"))?;
+            *sound = Some(expand_home(path)?);
+        }
+    }
+    if let Some(v) = t.get("status_file") {
+        let path = v.as_str().ok_or("`status_file` must be a path")?;
+        settings.status_file = Some(expand_home(path)?);
+    }
+    if let Some(v) = t.get("theme") {
+        settings.theme = match v.as_str() {
+            Some("auto") => None,
+            Some("dark") => Some(ColorScheme::Dark),
+            Some("light") => Some(ColorScheme::Light),
+            _ => return Err("`theme` must be \"auto\", \"dark\" or \"light\"".to_string()),
+        };
+    }
+    if let Some(v) = t.get("compositor") {
+        settings.compositor = match v.as_str() {
+            Some("auto") => None,
+            Some("niri") => Some(Compositor::Niri),
+            Some("sway") => Some(Compositor::Sway),
+            Some("hyprland") => Some(Compositor::Hyprland),
+            _ => {
+                return Err(
+                    "`compositor` must be \"auto\", \"niri\", \"sway\" or \"hyprland\"".to_string(),
+                );
+            }
+        };
+    }
+    if let Some(v) = t.get("quick_click_radius") {
+        settings.quick_click_radius = match v {
+            Value::Float(f) => *f,
+            Value::Integer(i) => *i as f64,
+            _ => return Err("`quick_click_radius` must be a number".to_string()),
+        };
+        if !(0.0..=1.0).contains(&settings.quick_click_radius) {
+            return Err("`quick_click_radius` must be between 0 and 1".to_string());
+        }
+    }
+    if let Some(v) = t.get("tooltips") {
+        settings.tooltips = v.as_bool().ok_or("`tooltips` must be a boolean")?;
+    }
+    if let Some(v) = t.get("show_shortcuts") {
+        settings.show_shortcuts = v.as_bool().ok_or("`show_shortcuts` must be a boolean")?;
+    }
+    if let Some(v) = t.get("gesture") {
+        settings.gesture = v.as_bool().ok_or("`gesture` must be a boolean")?;
+    }
+    if let Some(v) = t.get("click_through") {
+        settings.click_through = v.as_bool().ok_or("`click_through` must be a boolean")?;
+    }
+    if let Some(v) = t.get("dismiss_outside") {
+        settings.dismiss_outside = v.as_bool().ok_or("`dismiss_outside` must be a boolean")?;
+    }
+    if let Some(v) = t.get("label_overflow") {
+        settings.label_overflow = match v.as_str() {
+            Some("wrap") => LabelOverflow::Wrap,
+            Some("ellipsize") => LabelOverflow::Ellipsize,
+            _ => return Err("`label_overflow` must be \"wrap\" or \"ellipsize\"".to_string()),
+        };
+    }
+    if let Some(v) = t.get("reuse_position") {
+        settings.reuse_position = v.as_bool().ok_or("`reuse_position` must be a boolean")?;
+    }
+    if let Some(v) = t.get("spawn") {
+        settings.spawn = match v.as_str() {
+            Some("motion") => Spawn::Motion,
+            Some("pointer") => Spawn::Pointer,
+            Some("center") => Spawn::Center,
+            _ => return Err("`spawn` must be \"motion\", \"pointer\" or \"center\"".to_string()),
+        };
+    }
+    if let Some(v) = t.get("selection") {
+        settings.selection = match v.as_str() {
+            Some("click") => Selection::Click,
+            Some("release-drag") => Selection::ReleaseDrag,
+            _ => return Err("`selection` must be \"click\" or \"release-drag\"".to_string()),
+        };
+    }
+    if let Some(v) = t.get("fade_ms") {
+        settings.fade_ms = parse_ms(v).ok_or("`fade_ms` must be a non-negative integer")?;
+    }
+    if let Some(v) = t.get("submenu_anim_ms") {
+        settings.submenu_anim_ms =
+            parse_ms(v).ok_or("`submenu_anim_ms` must be a non-negative integer")?;
+    }
+    if let Some(v) = t.get("hover_scale") {
+        settings.hover_scale = match v {
+            Value::Float(f) => *f,
+            Value::Integer(i) => *i as f64,
+            _ => return Err("`hover_scale` must be a number".to_string()),
+        };
+        if settings.hover_scale <= 0.0 {
+            return Err("`hover_scale` must be positive".to_string());
+        }
+    }
+    if let Some(v) = t.get("hover_ms") {
+        settings.hover_ms = parse_ms(v).ok_or("`hover_ms` must be a non-negative integer")?;
+    }
+    if let Some(v) = t.get("long_press_ms") {
+        settings.long_press_ms =
+            parse_ms(v).ok_or("`long_press_ms` must be a non-negative integer")?;
+    }
+    if let Some(v) = t.get("idle_hide_ms") {
+        settings.idle_hide_ms =
+            parse_ms(v).ok_or("`idle_hide_ms` must be a non-negative integer")?;
+    }
+    if let Some(v) = t.get("toggle_debounce_ms") {
+        settings.toggle_debounce_ms =
+            parse_ms(v).ok_or("`toggle_debounce_ms` must be a non-negative integer")?;
+    }
+    if let Some(v) = t.get("key_delay_ms") {
+        settings.key_delay_ms =
+            parse_ms(v).ok_or("`key_delay_ms` must be a non-negative integer")?;
+    }
+    if let Some(v) = t.get("layout") {
+        settings.layout = parse_layout(v)?;
+    }
+    Ok(settings)
+}
+
+fn parse_layout(v: &Value) -> Result<MenuLayout, String> {
+    match v.as_str() {
+        Some("ring") => Ok(MenuLayout::Ring),
+        Some("wedge") => Ok(MenuLayout::Wedge),
+        Some("grid") => Ok(MenuLayout::Grid),
+        _ => Err("`layout` must be \"ring\", \"wedge\" or \"grid\"".to_string()),
+    }
+}
+
+fn expand_home(path: &str) -> Result<PathBuf, String> {
+    Ok(match path.strip_prefix("~/") {
+        Some(rest) => PathBuf::from(env::var_os("HOME").ok_or("$HOME is not set")?).join(rest),
+        None => PathBuf::from(path),
+    })
+}
+
+fn parse_ms(v: &Value) -> Option<u64> {
+    v.as_integer().and_then(|ms| u64::try_from(ms).ok())
+}
+
+fn parse_geometry(t: &Table) -> Result<Geometry, String> {
+    let number = |key: &str| -> Result<Option<f64>, String> {
+        match t.get(key) {
+            Some(Value::Float(f)) => Ok(Some(*f)),
+            Some(Value::Integer(i)) => Ok(Some(*i as f64)),
+            Some(_) => Err(format!("`{key}` must be a number")),
+            None => Ok(None),
+        }
+    };
+
+    let mut g = Geometry::default();
+    g.center_radius = number("center_radius")?.unwrap_or(g.center_radius);
+    g.ring_distance = number("ring_distance")?.unwrap_or(g.ring_distance);
+    g.item_radius = number("item_radius")?.unwrap_or(g.item_radius);
+    g.font_size = number("font_size")?.unwrap_or(g.font_size);
+    g.item_gap = number("item_gap")?.unwrap_or(g.item_gap);
+    // The deadzone follows the center circle unless set explicitly.
+    g.deadzone = number("deadzone")?.unwrap_or(g.center_radius);
+    g.deadzone_outer = number("deadzone_outer")?.unwrap_or(g.deadzone_outer);
+
+    let scale = number("scale")?.unwrap_or(1.0);
+    if scale <= 0.0 {
+        return Err("`scale` must be positive".to_string());
+    }
+    g.center_radius *= scale;
+    g.ring_distance *= scale;
+    g.item_radius *= scale;
+    g.font_size *= scale;
+    g.item_gap *= scale;
+    g.deadzone *= scale;
+    g.deadzone_outer *= scale;
+    g.scale = scale;
+
+    if g.center_radius <= 0.0
+        || g.item_radius <= 0.0
+        || g.font_size <= 0.0
+        || g.deadzone < 0.0
+        || g.deadzone_outer < 0.0
+        || g.item_gap < 0.0
+    {
+        return Err("sizes must be positive".to_string());
+    }
+    if g.ring_distance <= g.center_radius + g.item_radius {
+        return Err(format!(
+            "`ring_distance` ({}) must exceed `center_radius` + `item_radius` ({}) so items \
+             don't overlap the center",
+            g.ring_distance,
+            g.center_radius + g.item_radius
+        ));
+    }
+    Ok(g)
+}
+
+fn parse_theme(t: &Table, base: Theme) -> Result<Theme, String> {
+    let color = |key: &str| -> Result<Option<Color>, String> {
+        t.get(key).map(|v| parse_color(key, v)).transpose()
+    };
+
+    let mut theme = base;
+    theme.item = color("item")?.unwrap_or(theme.item);
+    theme.submenu = color("submenu")?.unwrap_or(theme.submenu);
+    theme.center = color("center")?.unwrap_or(theme.center);
+    theme.center_submenu = color("center_submenu")?.unwrap_or(theme.center_submenu);
+    theme.stroke = color("stroke")?.unwrap_or(theme.stroke);
+    theme.highlight = color("highlight")?.unwrap_or(theme.highlight);
+    theme.text = color("text")?.or(theme.text);
+    theme.glyph = color("glyph")?.unwrap_or(theme.glyph);
+    theme.anchor = color("anchor")?.unwrap_or(theme.anchor);
+    for (key, icon) in [
+        ("close_icon", &mut theme.close_icon),
+        ("back_icon", &mut theme.back_icon),
+    ] {
+        if let Some(v) = t.get(key) {
+            *icon = Some(
+                v.as_str()
+                    .ok_or_else(|| format!("`{key}` must be an icon name"))?
+                    .to_string(),
+            );
+        }
+    }
+    for (key, width) in [
+        ("stroke_width", &mut theme.stroke_width),
+        ("anchor_radius", &mut theme.anchor_radius),
+    ] {
+        if let Some(v) = t.get(key) {
+            *width = match v {
+                Value::Float(f) => *f,
+                Value::Integer(i) => *i as f64,
+                _ => return Err(format!("`{key}` must be a number")),
+            };
+            if *width < 0.0 {
+                return Err(format!("`{key}` must not be negative"));
+            }
+        }
+    }
+    Ok(theme)
+}
+
+// The stylesheet form of a theme: `role: color;` declarations, with CSS
+// comments and colors. Roles are the [theme] keys with dashes, plus the
+// names below for the ones whose key reads differently out of context.
+fn parse_stylesheet(text: &str, base: Theme) -> Result<Theme, String> {
+    let mut text = text.to_string();
+    while let Some(start) = text.find("/*") {
+        let end = text[start..]
+            .find("*/")
+            .map_or(text.len(), |end| start + end + 2);
+        text.replace_range(start..end, " ");
+    }
+
+    let mut theme = base;
+    for decl in text.split(';').map(str::trim).filter(|d| !d.is_empty()) {
+        let (role, value) = decl
+            .split_once(':')
+            .ok_or_else(|| format!("`{decl}` is not a `role: color` declaration"))?;
+        let (role, value) = (role.trim(), value.trim());
+        let color =
+            || parse_css_color(value).ok_or_else(|| format!("`{role}`: bad color `{value}`"));
+        match role {
+            "item" | "item-default" => theme.item = color()?,
+            "submenu" | "item-submenu" => theme.submenu = color()?,
+            "center" | "center-root" => theme.center = color()?,
+            "center-submenu" => theme.center_submenu = color()?,
+            "stroke" => theme.stroke = color()?,
+            "highlight" => theme.highlight = color()?,
+            "text" => theme.text = Some(color()?),
+            "glyph" => theme.glyph = color()?,
+            "anchor" => theme.anchor = color()?,
+            _ => {
+                return Err(format!(
+                    "unknown role `{role}`; expected item-default, item-submenu, center-root, \
+                     center-submenu, stroke, highlight, text, glyph or anchor"
+                ));
+            }
+        }
+    }
+    Ok(theme)
+}
+
+// `#rgb`, `#rrggbb`, `#rrggbbaa`, `rgb(r, g, b)` or `rgba(r, g, b, a)`, with
+// r, g and b from 0 to 255 and a from 0 to 1 as in CSS.
+fn parse_css_color(value: &str) -> Option<Color> {
+    if let Some(hex) = value.strip_prefix('#') {
+        let digits: Vec<u32> = hex.chars().map(|c| c.to_digit(16)).collect::<Option<_>>()?;
+        let bytes: Vec<f64> = match digits.len() {
+            3 => digits.iter().map(|&d| (d * 17) as f64).collect(),
+            6 | 8 => digits
+                .chunks(2)
+                .map(|pair| (pair[0] * 16 + pair[1]) as f64)
+                .collect(),
+            _ => return None,
+        };
+        return Some(Color {
+            r: bytes[0] / 255.0,
+            g: bytes[1] / 255.0,
+            b: bytes[2] / 255.0,
+            a: bytes.get(3).map_or(1.0, |a| a / 255.0),
+        });
+    }
+    let (name, args) = value.strip_suffix(')')?.split_once('(')?;
+    let parts: Vec<f64> = args
+        .split(',')
+        .map(|part| part.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    let a = match (name.trim(), parts.len()) {
+        ("rgb", 3) => 1.0,
+        ("rgba", 4) => parts[3],
+        _ => return None,
+    };
+    if !parts[..3].iter().all(|c| (0.0..=255.0).contains(c)) || !(0.0..=1.0).contains(&a) {
+        return None;
+    }
+    Some(Color {
+        r: parts[0] / 255.0,
+        g: parts[1] / 255.0,
+        b: parts[2] / 255.0,
+        a,
+    })
+}
+
+fn build_menu(menus: &Table, name: &str, stack: &mut Vec<String>) -> Result<Menu, String> {
+    if stack.iter().any(|n| n == name) {
+        return Err(format!("submenu cycle: {} -> {}", stack.join(" -> "), name));
+    }
+
+    let menu = menus
+        .get(name)
+        .and_then(Value::as_table)
+        .ok_or_else(|| format!("menu `{name}` is not defined"))?;
+    let entries = match menu.get("items") {
+        Some(v) => v
+            .as_array()
+            .ok_or_else(|| format!("menu `{name}`: `items` must be an array"))?
+            .as_slice(),
+        None => &[],
+    };
+    let layout = match menu.get("layout") {
+        Some(v) => Some(parse_layout(v).map_err(|e| format!("menu `{name}`: {e}"))?),
+        None => None,
+    };
+    let sticky = match menu.get("sticky") {
+        Some(v) => v
+            .as_bool()
+            .ok_or_else(|| format!("menu `{name}`: `sticky` must be a boolean"))?,
+        None => false,
+    };
+
+    stack.push(name.to_string());
+    let mut items = Vec::with_capacity(entries.len());
+    for (i, entry) in entries.iter().enumerate() {
+        let entry = entry
+            .as_table()
+            .ok_or_else(|| format!("menu `{name}`: item {i} must be a table"))?;
+        let item =
+            build_item(menus, entry, stack).map_err(|e| format!("menu `{name}` item {i}: {e}"))?;
+        items.push(item);
+    }
+    stack.pop();
+
+    Ok(Menu {
+        name: name.to_string(),
+        items,
+        layout,
+        sticky,
+        page: 0,
+    })
+}
+
+fn build_item(menus: &Table, entry: &Table, stack: &mut Vec<String>) -> Result<MenuItem, String> {
+    if let Some(v) = entry.get("blank")
+        && v.as_bool().ok_or("`blank` must be a boolean")?
+    {
+        if entry.contains_key("cmd") || entry.contains_key("submenu") {
+            return Err("a blank item can't have `cmd` or `submenu`".to_string());
+        }
+        return Ok(MenuItem {
+            label: String::new(),
+            kind: ItemKind::Blank,
+            color: None,
+            text_color: None,
+            font_size: None,
+            icon: None,
+            image: None,
+            enabled: false,
+            show_when: None,
+            description: None,
+            shift_action: None,
+            ctrl_action: None,
+        });
+    }
+
+    let label = entry
+        .get("label")
+        .and_then(Value::as_str)
+        .ok_or("missing `label`")?
+        .to_string();
+
+    let kind = if let Some(v) = entry.get("generate") {
+        if ["submenu", "cmd", "open"]
+            .iter()
+            .any(|k| entry.contains_key(*k))
+        {
+            return Err(format!(
+                "`{label}`: `generate` can't be combined with `cmd`, `submenu` or `open`"
+            ));
+        }
+        let generator = v
+            .as_table()
+            .ok_or_else(|| format!("`{label}`: `generate` must be a table"))
+            .and_then(|t| parse_generator(t).map_err(|e| format!("`{label}`: {e}")))?;
+        ItemKind::Generated(generator)
+    } else if let Some(v) = entry.get("cycle") {
+        if ["submenu", "cmd", "open"]
+            .iter()
+            .any(|k| entry.contains_key(*k))
+        {
+            return Err(format!(
+                "`{label}`: `cycle` can't be combined with `cmd`, `submenu` or `open`"
+            ));
+        }
+        let entries = v
+            .as_array()
+            .filter(|entries| entries.len() >= 2)
+            .ok_or_else(|| format!("`{label}`: `cycle` must be an array of at least 2 tables"))?;
+        let mut actions = Vec::with_capacity(entries.len());
+        let mut labels = Vec::with_capacity(entries.len());
+        for (i, entry) in entries.iter().enumerate() {
+            let t = entry
+                .as_table()
+                .ok_or_else(|| format!("`{label}`: `cycle` entry {i} must be a table"))?;
+            let shown = t
+                .get("label")
+                .and_then(Value::as_str)
+                .ok_or_else(|| format!("`{label}`: `cycle` entry {i} is missing `label`"))?;
+            labels.push(shown.to_string());
+            actions.push(parse_action(t).map_err(|e| format!("`{label}` `{shown}`: {e}"))?);
+        }
+        ItemKind::Cycle { actions, labels }
+    } else if let Some(v) = entry.get("open") {
+        if entry.contains_key("submenu") || entry.contains_key("cmd") {
+            return Err(format!(
+                "`{label}`: `open` can't be combined with `cmd` or `submenu`"
+            ));
+        }
+        let name = v
+            .as_str()
+            .ok_or_else(|| format!("`{label}`: `open` must be a menu name"))?;
+        if !menus.contains_key(name) {
+            return Err(format!(
+                "`{label}`: menu `{name}` has no [menus.{name}] table"
+            ));
+        }
+        ItemKind::OpenMenu {
+            name: name.to_string(),
+        }
+    } else {
+        match (entry.get("submenu"), entry.get("cmd")) {
+            (Some(_), Some(_)) => {
+                return Err(format!("`{label}` has both `submenu` and `cmd`"));
+            }
+            (Some(sub), None) => {
+                let sub = sub
+                    .as_str()
+                    .ok_or_else(|| format!("`{label}`: `submenu` must be a menu name"))?;
+                if !menus.contains_key(sub) {
+                    return Err(format!(
+                        "`{label}`: submenu `{sub}` has no [menus.{sub}] table"
+                    ));
+                }
+                let on_click = match entry.get("on_click") {
+                    Some(v) => Some(
+                        v.as_table()
+                            .ok_or_else(|| format!("`{label}`: `on_click` must be a table"))
+                            .and_then(|t| parse_action(t).map_err(|e| format!("`{label}`: {e}")))?,
+                    ),
+                    None => None,
+                };
+                let quick_click = match entry.get("quick_click") {
+                    Some(v) => v
+                        .as_bool()
+                        .ok_or_else(|| format!("`{label}`: `quick_click` must be a boolean"))?,
+                    None => true,
+                };
+                let menu = build_menu(menus, sub, stack)?;
+                ItemKind::Submenu {
+                    menu,
+                    on_click,
+                    quick_click,
+                }
+            }
+            (None, Some(_)) => {
+                ItemKind::Action(parse_action(entry).map_err(|e| format!("`{label}`: {e}"))?)
+            }
+            (None, None) => {
+                return Err(format!(
+                    "`{label}` needs one of `cmd`, `submenu`, `open`, `generate` or `cycle`"
+                ));
+            }
+        }
+    };
+
+    let color = match entry.get("color") {
+        Some(v) => Some(parse_color("color", v).map_err(|e| format!("`{label}`: {e}"))?),
+        None => None,
+    };
+
+    let font_size = match entry.get("font_size") {
+        Some(Value::Float(f)) if *f > 0.0 => Some(*f),
+        Some(Value::Integer(i)) if *i > 0 => Some(*i as f64),
+        Some(_) => return Err(format!("`{label}`: `font_size` must be a positive number")),
+        None => None,
+    };
+    let text_color = match entry.get("text_color") {
+        Some(v) => Some(parse_color("text_color", v).map_err(|e| format!("`{label}`: {e}"))?),
+        None => None,
+    };
+
+    let enabled = match entry.get("enabled") {
+        Some(v) => v
+            .as_bool()
+            .ok_or_else(|| format!("`{label}`: `enabled` must be a boolean"))?,
+        None => true,
+    };
+
+    let description = match entry.get("description") {
+        Some(v) => Some(
+            v.as_str()
+                .ok_or_else(|| format!("`{label}`: `description` must be a string"))?
+                .to_string(),
+        ),
+        None => None,
+    };
+
+    let show_when = match entry.get("show_when") {
+        Some(v) => Some(parse_condition(v).map_err(|e| format!("`{label}`: {e}"))?),
+        None => None,
+    };
+
+    let icon = match entry.get("icon") {
+        Some(v) => Some(
+            v.as_str()
+                .ok_or_else(|| format!("`{label}`: `icon` must be an icon name"))?
+                .to_string(),
+        ),
+        None => None,
+    };
+    let image = match entry.get("image") {
+        Some(v) => {
+            Some(expand_home(v.as_str().ok_or_else(|| {
+                format!("`{label}`: `image` must be a path")
+            })?)?)
+        }
+        None => None,
+    };
+
+    let mut alternatives = [None, None];
+    for (key, action) in ["shift_action", "ctrl_action"]
+        .iter()
+        .zip(&mut alternatives)
+    {
+        if let Some(v) = entry.get(*key) {
+            *action = Some(
+                v.as_table()
+                    .ok_or_else(|| format!("`{label}`: `{key}` must be a table"))
+                    .and_then(|t| parse_action(t).map_err(|e| format!("`{label}`: {key}: {e}")))?,
+            );
+        }
+    }
+    let [shift_action, ctrl_action] = alternatives;
+
+    Ok(MenuItem {
+        label,
+        kind,
+        color,
+        text_color,
+        font_size,
+        icon,
+        image,
+        enabled,
+        show_when,
+        description,
+        shift_action,
+        ctrl_action,
+    })
+}
+
+fn parse_action(t: &Table) -> Result<Action, String> {
+    let cmd = t
+        .get("cmd")
+        .and_then(Value::as_str)
+        .ok_or("`cmd` must be a string")?
+        .to_string();
+    let close_on_click = match t.get("close_on_click") {
+        Some(v) => v.as_bool().ok_or("`close_on_click` must be a boolean")?,
+        None => false,
+    };
+    let key_delay_ms = match t.get("key_delay_ms") {
+        Some(v) => Some(parse_ms(v).ok_or("`key_delay_ms` must be a non-negative integer")?),
+        None => None,
+    };
+    let sound = match t.get("sound") {
+        Some(v) => Some(expand_home(v.as_str().ok_or("`sound` must be a path")?)?),
+        None => None,
+    };
+    let confirm = match t.get("confirm") {
+        Some(v) => v.as_bool().ok_or("`confirm` must be a boolean")?,
+        None => false,
+    };
+    let delay_before_ms = match t.get("delay_before_ms") {
+        Some(v) => parse_ms(v).ok_or("`delay_before_ms` must be a non-negative integer")?,
+        None => 0,
+    };
+    let cwd = match t.get("cwd") {
+        Some(v) => Some(expand_home(v.as_str().ok_or("`cwd` must be a path")?)?),
+        None => None,
+    };
+    let env = match t.get("env") {
+        Some(v) => v
+            .as_table()
+            .ok_or("`env` must be a table of strings")?
+            .iter()
+            .map(|(name, value)| match value.as_str() {
+                Some(value) => Ok((name.clone(), value.to_string())),
+                None => Err(format!("`env.{name}` must be a string")),
+            })
+            .collect::<Result<_, _>>()?,
+        None => Vec::new(),
+    };
+    Ok(Action {
+        cmd,
+        close_on_click,
+        key_delay_ms,
+        sound,
+        confirm,
+        delay_before_ms,
+        cwd,
+        env,
+    })
+}
+
+fn parse_generator(t: &Table) -> Result<Generator, String> {
+    let string = |key: &str| -> Result<Option<String>, String> {
+        match t.get(key) {
+            Some(v) => Ok(Some(
+                v.as_str()
+                    .ok_or_else(|| format!("`generate.{key}` must be a string"))?
+                    .to_string(),
+            )),
+            None => Ok(None),
+        }
+    };
+    let close_on_click = match t.get("close_on_click") {
+        Some(v) => v
+            .as_bool()
+            .ok_or("`generate.close_on_click` must be a boolean")?,
+        None => true,
+    };
+    Ok(Generator {
+        cmd: string("cmd")?.ok_or("`generate` needs a `cmd`")?,
+        label: string("label")?,
+        action: string("action")?.ok_or("`generate` needs an `action`")?,
+        close_on_click,
+    })
+}
+
+fn parse_condition(v: &Value) -> Result<Condition, String> {
+    let text = v.as_str().ok_or("`show_when` must be a string")?;
+    let (negate, name) = match text.strip_prefix('!') {
+        Some(name) => (true, name),
+        None => (false, text),
+    };
+    let state = match name {
+        "window" => WindowState::Focused,
+        "floating" => WindowState::Floating,
+        "tiling" => WindowState::Tiling,
+        "fullscreen" => WindowState::Fullscreen,
+        _ => {
+            return Err(format!(
+                "unknown `show_when` condition `{name}` (expected window, floating, tiling \
+                 or fullscreen, optionally prefixed with `!`)"
+            ));
+        }
+    };
+    Ok(Condition { state, negate })
+}
+
+fn parse_color(key: &str, v: &Value) -> Result<Color, String> {
+    let parts = v
+        .as_array()
+        .ok_or_else(|| format!("`{key}` must be an array [r, g, b] or [r, g, b, a]"))?;
+    let mut c = [0.0, 0.0, 0.0, 1.0];
+    if parts.len() != 3 && parts.len() != 4 {
+        return Err(format!("`{key}` must have 3 or 4 components"));
+    }
+    for (slot, part) in c.iter_mut().zip(parts) {
+        *slot = match part {
+            Value::Float(f) => *f,
+            Value::Integer(i) => *i as f64,
+            _ => return Err(format!("`{key}` components must be numbers")),
+        };
+    }
+    Ok(Color {
+        r: c[0],
+        g: c[1],
+        b: c[2],
+        a: c[3],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A fresh directory holding `files`, named after the test using it.
+    fn config_dir(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("waydo-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for (name, text) in files {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn include_cycles_are_refused() {
+        let dir = config_dir(
+            "include-cycle",
+            &[
+                ("menu.toml", "include = [\"b.toml\"]\n[menus.root]\n"),
+                ("b.toml", "include = [\"./menu.toml\"]\n[menus.b]\n"),
+            ],
+        );
+        let err = load_file(&dir.join("menu.toml")).unwrap_err();
+        assert!(
+            err.contains("menu.toml is included more than once"),
+            "{err}"
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn missing_includes_are_named() {
+        let dir = config_dir(
+            "include-missing",
+            &[("menu.toml", "include = [\"gone.toml\"]\n[menus.root]\n")],
+        );
+        let err = load_file(&dir.join("menu.toml")).unwrap_err();
+        let missing = dir.join("gone.toml");
+        assert_eq!(
+            err,
+            format!("included file {} not found", missing.display())
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    // A file included from another is still found relative to the main
+    // config, not to the file including it.
+    #[test]
+    fn nested_includes_resolve_from_the_config_directory() {
+        let dir = config_dir(
+            "include-nested",
+            &[
+                (
+                    "menu.toml",
+                    "include = [\"menus/apps.toml\"]\n[menus.root]\n",
+                ),
+                (
+                    "menus/apps.toml",
+                    "include = [\"menus/games.toml\"]\n[menus.apps]\n",
+                ),
+                ("menus/games.toml", "[menus.games]\n"),
+            ],
+        );
+        let config = load_file(&dir.join("menu.toml")).unwrap();
+        let mut names: Vec<&str> = config.menus.keys().map(String::as_str).collect();
+        names.sort();
+        assert_eq!(names, ["apps", "games", "root"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    fn rgba(c: Color) -> [f64; 4] {
+        [c.r * 255.0, c.g * 255.0, c.b * 255.0, c.a].map(|v| (v * 1000.0).round() / 1000.0)
+    }
+
+    #[test]
+    fn css_colors_parse_in_every_form() {
+        let color = |s| parse_css_color(s).map(rgba);
+        assert_eq!(color("#f80"), Some([255.0, 136.0, 0.0, 1.0]));
+        assert_eq!(color("#1e90ff"), Some([30.0, 144.0, 255.0, 1.0]));
+        assert_eq!(color("#1e90ff80"), Some([30.0, 144.0, 255.0, 0.502]));
+        assert_eq!(color("rgb(30, 144, 255)"), Some([30.0, 144.0, 255.0, 1.0]));
+        assert_eq!(
+            color("rgba(30,144,255,0.5)"),
+            Some([30.0, 144.0, 255.0, 0.5])
+        );
+        assert_eq!(color("rgba(0, 0, 0, 0)"), Some([0.0, 0.0, 0.0, 0.0]));
+    }
+
+    #[test]
+    fn css_colors_out_of_range_or_garbled_are_refused() {
+        for bad in [
+            "#12",
+            "#12345",
+            "#ggg",
+            "1e90ff",
+            "rgb(256, 0, 0)",
+            "rgb(-1, 0, 0)",
+            "rgba(0, 0, 0, 1.5)",
+            "rgba(0, 0, 0, -0.1)",
+            "rgba(0, 0, 0, nan)",
+            "rgb(0, 0)",
+            "rgba(0, 0, 0)",
+            "hsl(0, 0, 0)",
+            "rgb(a, b, c)",
+            "rgb(0, 0, 0",
+        ] {
+            assert!(parse_css_color(bad).is_none(), "{bad}");
+        }
+        let err = parse_stylesheet("item: rgba(0, 0, 0, 2);", Theme::default()).unwrap_err();
+        assert_eq!(err, "`item`: bad color `rgba(0, 0, 0, 2)`");
+        assert!(parse_stylesheet("item #fff", Theme::default()).is_err());
+        assert!(parse_stylesheet("shadow: #fff;", Theme::default()).is_err());
+    }
+
+    // The stylesheet is applied over [theme], to both palettes; what it
+    // doesn't mention keeps its [theme] value.
+    #[test]
+    fn stylesheet_roles_override_the_theme_table() {
+        let dir = config_dir(
+            "stylesheet",
+            &[(
+                "style.css",
+                "/* roles */\nitem-default: #ff0000;\ncenter-root: rgba(0, 0, 255, 0.5);\n",
+            )],
+        );
+        let css = dir.join("style.css");
+        let text = format!(
+            "[menus.root]\n[theme]\nitem = [0, 1, 0]\nstroke = [0, 1, 0]\n\
+             stylesheet = \"{}\"\n",
+            css.display()
+        );
+        let settings = parse_config(&text).unwrap().settings;
+        for theme in [&settings.dark_theme, &settings.light_theme] {
+            assert_eq!(rgba(theme.item), [255.0, 0.0, 0.0, 1.0]);
+            assert_eq!(rgba(theme.center), [0.0, 0.0, 255.0, 0.5]);
+            assert_eq!(rgba(theme.stroke), [0.0, 255.0, 0.0, 1.0]);
+        }
+        assert_eq!(settings.stylesheet, Some(css));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn ring_distance_must_clear_the_center_and_items() {
+        let geometry = |extra: &str| {
+            parse_config(&format!(
+                "[menus.root]\n[geometry]\ncenter_radius = 20\nitem_radius = 30\n{extra}"
+            ))
+            .map(|config| config.settings.geometry.ring_distance)
+        };
+        assert_eq!(geometry("ring_distance = 51"), Ok(51.0));
+        assert_eq!(geometry("ring_distance = 60\nscale = 2"), Ok(120.0));
+        assert_eq!(
+            geometry("ring_distance = 50").unwrap_err(),
+            "[geometry]: `ring_distance` (50) must exceed `center_radius` + `item_radius` (50) \
+             so items don't overlap the center"
+        );
+    }
+}
@@ -1,525 +1,53 @@
 use gtk::gdk;
+use gtk::gdk_pixbuf::Pixbuf;
+use gtk::gio;
 use gtk::glib;
+use gtk::pango;
 use gtk::prelude::*;
 use gtk::{Application, ApplicationWindow, DrawingArea};
 
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::env;
+use std::fs;
 use std::io::{BufRead, BufReader, Write};
 use std::os::unix::net::{UnixListener, UnixStream};
-use std::path::Path;
-use std::process::Command;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::mpsc::{self, Sender};
 use std::thread;
+use std::time::{Duration, Instant};
 
 use gtk4_layer_shell::{Edge, KeyboardMode, Layer, LayerShell};
 
-#[derive(Clone, Copy)]
-struct Action {
-    cmd: &'static str,
-    close_on_click: bool,
-}
-
-#[derive(Clone, Copy)]
-enum ItemKind {
-    Action(Action),
-    Submenu {
-        items: &'static [MenuItem],
-        on_click: Option<Action>,
-    },
-}
-
-#[derive(Clone, Copy)]
-struct Color {
-    r: f64,
-    g: f64,
-    b: f64,
-    a: f64,
-}
-
-const DEFAULT_ITEM_COLOR: Color = Color {
-    r: 0.15,
-    g: 0.15,
-    b: 0.15,
-    a: 0.80,
-};
+mod actions;
+mod config;
+mod usage;
 
-const SUBMENU_ITEM_COLOR: Color = Color {
-    r: 0.31,
-    g: 0.26,
-    b: 0.40,
-    a: 0.90,
+use config::{
+    Action, Color, ColorScheme, Compositor, Condition, Generator, Geometry, ItemKind,
+    LabelOverflow, Menu, MenuItem, MenuLayout, Selection, Settings, Spawn, Theme, WindowState,
 };
 
-#[derive(Clone, Copy)]
-struct MenuItem {
-    label: &'static str,
-    kind: ItemKind,
-    color: Color,
-}
-
-const CENTER_RADIUS: f64 = 18.0;
-const ITEM_RING_DISTANCE: f64 = 86.0;
-const ITEM_RADIUS: f64 = 35.0;
-const FONT_SIZE: f64 = 13.0;
-
-static APP_MENU: &[MenuItem] = &[
-    MenuItem {
-        label: "Neovide",
-        kind: ItemKind::Action(Action {
-            cmd: "spawn -- fish -c ~/.local/bin/neovide-focus",
-            close_on_click: true,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Zen",
-        kind: ItemKind::Action(Action {
-            cmd: "spawn -- flatpak run app.zen_browser.zen",
-            close_on_click: true,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Files",
-        kind: ItemKind::Action(Action {
-            cmd: "spawn -- nautilus",
-            close_on_click: true,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Zotero",
-        kind: ItemKind::Action(Action {
-            cmd: "spawn -- flatpak run org.zotero.Zotero",
-            close_on_click: true,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Btop",
-        kind: ItemKind::Action(Action {
-            cmd: "spawn -- alacritty --title 'Btop' -e btop",
-            close_on_click: true,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-];
-
-static ACTION_MENU: &[MenuItem] = &[
-    MenuItem {
-        label: "App",
-        kind: ItemKind::Submenu {
-            items: APP_MENU,
-            on_click: None,
-        },
-        color: SUBMENU_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Fullscreen",
-        kind: ItemKind::Action(Action {
-            cmd: "fullscreen-window",
-            close_on_click: false,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Maximize",
-        kind: ItemKind::Action(Action {
-            cmd: "maximize-window-to-edges",
-            close_on_click: false,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Float",
-        kind: ItemKind::Action(Action {
-            cmd: "toggle-window-floating",
-            close_on_click: false,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Close",
-        kind: ItemKind::Action(Action {
-            cmd: "close-window",
-            close_on_click: true,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Screenshot",
-        kind: ItemKind::Action(Action {
-            cmd: "screenshot -p false",
-            close_on_click: true,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-];
-
-static MOVEMENT_MENU: &[MenuItem] = &[
-    MenuItem {
-        label: "Up",
-        kind: ItemKind::Action(Action {
-            cmd: "move-window-to-workspace-up",
-            close_on_click: false,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Right",
-        kind: ItemKind::Action(Action {
-            cmd: "swap-window-right",
-            close_on_click: false,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Down",
-        kind: ItemKind::Action(Action {
-            cmd: "move-window-to-workspace-down",
-            close_on_click: false,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Left",
-        kind: ItemKind::Action(Action {
-            cmd: "swap-window-left",
-            close_on_click: false,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-];
-
-static FOCUS_MENU: &[MenuItem] = &[
-    MenuItem {
-        label: "Up",
-        kind: ItemKind::Action(Action {
-            cmd: "focus-workspace-up",
-            close_on_click: false,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Switch",
-        kind: ItemKind::Action(Action {
-            cmd: "switch-focus-between-floating-and-tiling",
-            close_on_click: false,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Right",
-        kind: ItemKind::Action(Action {
-            cmd: "focus-column-right",
-            close_on_click: false,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Move",
-        kind: ItemKind::Submenu {
-            items: MOVEMENT_MENU,
-            on_click: None,
-        },
-        color: SUBMENU_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Down",
-        kind: ItemKind::Action(Action {
-            cmd: "focus-workspace-down",
-            close_on_click: false,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Move",
-        kind: ItemKind::Submenu {
-            items: MOVEMENT_MENU,
-            on_click: None,
-        },
-        color: SUBMENU_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Left",
-        kind: ItemKind::Action(Action {
-            cmd: "focus-column-left",
-            close_on_click: false,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Switch",
-        kind: ItemKind::Action(Action {
-            cmd: "switch-focus-between-floating-and-tiling",
-            close_on_click: false,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-];
-
-static MISC_MENU: &[MenuItem] = &[
-    MenuItem {
-        label: "PageUp",
-        kind: ItemKind::Action(Action {
-            cmd: "key-pageup",
-            close_on_click: false,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Undo",
-        kind: ItemKind::Action(Action {
-            cmd: "key-ctrl-z",
-            close_on_click: false,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Redo",
-        kind: ItemKind::Action(Action {
-            cmd: "key-ctrl-shift-z",
-            close_on_click: false,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Delete",
-        kind: ItemKind::Action(Action {
-            cmd: "key-delete",
-            close_on_click: true,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "PageDown",
-        kind: ItemKind::Action(Action {
-            cmd: "key-pagedown",
-            close_on_click: false,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Copy",
-        kind: ItemKind::Action(Action {
-            cmd: "key-ctrl-c",
-            close_on_click: true,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Paste",
-        kind: ItemKind::Action(Action {
-            cmd: "key-ctrl-v",
-            close_on_click: true,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Duplicate",
-        kind: ItemKind::Action(Action {
-            cmd: "key-ctrl-d",
-            close_on_click: true,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-];
-
-static BRUSH_MENU: &[MenuItem] = &[
-    MenuItem {
-        label: "Black",
-        kind: ItemKind::Action(Action {
-            cmd: "key-ctrl-f1",
-            close_on_click: true,
-        }),
-        color: Color {
-            r: 0.0,
-            g: 0.0,
-            b: 0.0,
-            a: 0.90,
-        },
-    },
-    MenuItem {
-        label: "Blue",
-        kind: ItemKind::Action(Action {
-            cmd: "key-ctrl-f5",
-            close_on_click: true,
-        }),
-        color: Color {
-            r: 0.20,
-            g: 0.45,
-            b: 0.95,
-            a: 0.90,
-        },
-    },
-    MenuItem {
-        label: "Green",
-        kind: ItemKind::Action(Action {
-            cmd: "key-ctrl-f6",
-            close_on_click: true,
-        }),
-        color: Color {
-            r: 0.18,
-            g: 0.72,
-            b: 0.30,
-            a: 0.90,
-        },
-    },
-    MenuItem {
-        label: "Yellow",
-        kind: ItemKind::Action(Action {
-            cmd: "key-ctrl-f7",
-            close_on_click: true,
-        }),
-        color: Color {
-            r: 0.95,
-            g: 0.83,
-            b: 0.20,
-            a: 0.90,
-        },
-    },
-    MenuItem {
-        label: "Orange",
-        kind: ItemKind::Action(Action {
-            cmd: "key-ctrl-f8",
-            close_on_click: true,
-        }),
-        color: Color {
-            r: 0.96,
-            g: 0.56,
-            b: 0.18,
-            a: 0.90,
-        },
-    },
-    MenuItem {
-        label: "Red",
-        kind: ItemKind::Action(Action {
-            cmd: "key-ctrl-f9",
-            close_on_click: true,
-        }),
-        color: Color {
-            r: 0.88,
-            g: 0.24,
-            b: 0.24,
-            a: 0.90,
-        },
-    },
-];
-
-static SELECTOR_MENU: &[MenuItem] = &[
-    MenuItem {
-        label: "Polygon",
-        kind: ItemKind::Action(Action {
-            cmd: "key-f1",
-            close_on_click: true,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Single",
-        kind: ItemKind::Action(Action {
-            cmd: "key-f3",
-            close_on_click: true,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Intersecting",
-        kind: ItemKind::Action(Action {
-            cmd: "key-f4",
-            close_on_click: true,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-];
-
-static TOOLS_MENU: &[MenuItem] = &[
-    MenuItem {
-        label: "Vertical",
-        kind: ItemKind::Action(Action {
-            cmd: "key-f5",
-            close_on_click: true,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Zoom",
-        kind: ItemKind::Action(Action {
-            cmd: "key-f7",
-            close_on_click: true,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Laser",
-        kind: ItemKind::Action(Action {
-            cmd: "key-f8",
-            close_on_click: true,
-        }),
-        color: DEFAULT_ITEM_COLOR,
-    },
-];
-
-static ROOT_MENU: &[MenuItem] = &[
-    MenuItem {
-        label: "Action",
-        kind: ItemKind::Submenu {
-            items: ACTION_MENU,
-            on_click: None,
-        },
-        color: SUBMENU_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Workspace",
-        kind: ItemKind::Submenu {
-            items: FOCUS_MENU,
-            on_click: None,
-        },
-        color: SUBMENU_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Tools",
-        kind: ItemKind::Submenu {
-            items: TOOLS_MENU,
-            on_click: Some(Action {
-                cmd: "key-ctrl-6 f6",
-                close_on_click: false,
-            }),
-        },
-        color: SUBMENU_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Selector",
-        kind: ItemKind::Submenu {
-            items: SELECTOR_MENU,
-            on_click: Some(Action {
-                cmd: "key-ctrl-5 f2",
-                close_on_click: false,
-            }),
-        },
-        color: SUBMENU_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Brush",
-        kind: ItemKind::Submenu {
-            items: BRUSH_MENU,
-            on_click: Some(Action {
-                cmd: "key-ctrl-1",
-                close_on_click: false,
-            }),
-        },
-        color: SUBMENU_ITEM_COLOR,
-    },
-    MenuItem {
-        label: "Misc",
-        kind: ItemKind::Submenu {
-            items: MISC_MENU,
-            on_click: None,
-        },
-        color: SUBMENU_ITEM_COLOR,
-    },
-];
+const FONT_FAMILY: &str = "Sans";
+// Horizontal room left between a label and its item's edge.
+const LABEL_PADDING: f64 = 10.0;
+const TOOLTIP_MAX_WIDTH: f64 = 320.0;
+const DISABLED_ALPHA: f64 = 0.35;
+const SHADOW_OFFSET: f64 = 2.0;
+const SHADOW_SPREAD: f64 = 5.0;
+// Animation frame interval.
+const TICK_MS: u64 = 16;
+// Pointer motion this soon after the menu is mapped only says where the
+// pointer is; it anchors the menu there once the time is up.
+const ANCHOR_GRACE: Duration = Duration::from_millis(80);
+// How long a `confirm` action stays armed for its second click.
+const CONFIRM_WINDOW: Duration = Duration::from_secs(2);
+// How long `--autostart` waits for a freshly started daemon to listen.
+const AUTOSTART_WAIT: Duration = Duration::from_secs(3);
+// How long opening the menu waits on the compositor to say what has focus
+// before going ahead without it.
+const FOCUS_WAIT: Duration = Duration::from_millis(250);
 
 #[derive(Debug, Default)]
 struct State {
@@ -538,22 +66,417 @@ struct State {
     root_cx: f64,
     root_cy: f64,
 
+    // Logical size of the surface, for `keep_on_screen`
+    surface: (f64, f64),
+
+    // Root anchor of the previous showing, for `reuse_position`
+    last_root: Option<(f64, f64)>,
+
+    // Connector of the output the menu was last shown on
+    output: Option<glib::GString>,
+
     // Path root -> submenu
     path: Vec<usize>,
+
+    // Menus that `open` items replaced, most recent last; going back from
+    // the root returns to them
+    chain: Vec<String>,
+
+    // Item under the pointer, or selected by keyboard navigation
+    highlight: Option<usize>,
+
+    // Radians the ring was turned clockwise with the mouse wheel
+    rotation: f64,
+
+    // Gesture mode: set once the pointer is back inside the ring, so a
+    // single crossing selects only once
+    gesture_armed: bool,
+
+    // The touch in progress is the one that anchored the menu
+    touch_anchored: bool,
+
+    // When a menu waiting for motion to anchor was mapped, and the last
+    // pointer position seen within `ANCHOR_GRACE` of that
+    mapped_at: Option<Instant>,
+    early_pointer: Option<(f64, f64)>,
+
+    // When the mouse button being held went down, for `long_press_ms`
+    pressed_at: Option<Instant>,
+
+    // With `selection = "release-drag"`, the button being held started a
+    // flick from the center, and letting go picks the highlighted item; and
+    // whether that press is what anchored the menu
+    press_flick: bool,
+    press_anchored: bool,
+
+    // `confirm` action waiting for its second click
+    armed: Option<Armed>,
+
+    // Loaded menu trees by name, and the one currently shown
+    menus: HashMap<String, Menu>,
+    active: String,
+    settings: Settings,
+
+    // The active menu as shown: items whose `show_when` doesn't hold for the
+    // window focused at open time are left out. Rebuilt by refresh_view.
+    view: Menu,
+
+    // Fade animation: current opacity factor and the ramp in progress
+    opacity: f64,
+    fade: Option<Fade>,
+    nav: Option<NavAnim>,
+    // Current size of each item of the shown menu, easing towards
+    // `hover_scale` for the highlighted one and 1 for the rest. Drawing only;
+    // hit-testing always uses the base geometry.
+    grow: Vec<f64>,
+
+    // Item icons resolved from the icon theme, by icon name, rasterized at
+    // the surface's scale factor (device pixels per logical pixel)
+    icons: HashMap<String, Pixbuf>,
+    scale_factor: i32,
+    // Item `image` files by path, decoded at the same scale
+    images: HashMap<PathBuf, Pixbuf>,
+
+    // Startup warnings shown under the menu (e.g. missing ydotool)
+    warnings: Vec<String>,
+
+    // When a toggle last opened or closed the menu, for `toggle_debounce_ms`
+    last_toggle: Option<Instant>,
+
+    // Last input event on the surface, and whether a timer is waiting to
+    // check it against `idle_hide_ms`
+    last_input: Option<Instant>,
+    idle_timer: bool,
+
+    // Last line written to `status_file`
+    published: String,
+
+    // Rectangles the surface currently takes input in; empty for all of it
+    input_region: Vec<(i32, i32, i32, i32)>,
+
+    // Window changes waiting for the state borrow to be released
+    pending: Vec<WindowOp>,
+
+    // Light/dark preference reported by the desktop, for `theme = "auto"`
+    system_scheme: ColorScheme,
+
+    // `waydo-keyboard` turned the keyboard grab off until the menu hides
+    keyboard_released: bool,
+
+    // Activation counts behind `frequent`
+    usage: usage::Usage,
+
+    // Which action of each `cycle` item ran last, by `usage::key`
+    cycles: HashMap<String, usize>,
+
+    // Watches the theme's `stylesheet`, and which file it is
+    stylesheet_monitor: Option<(PathBuf, gio::FileMonitor)>,
+
+    // A generator item whose command is still running
+    generating: Option<Generating>,
+
+    // A menu waiting on the compositor's answer to open
+    opening: Option<Opening>,
+
+    // The focused window as of the last opening, for `show_when`
+    window: actions::WindowContext,
+}
+
+#[derive(Debug)]
+struct Opening {
+    menu: String,
+    since: Instant,
+    focus: mpsc::Receiver<actions::Focus>,
+}
+
+// Where a generator item was activated, to open its menu there once the
+// command's entries arrive.
+#[derive(Debug)]
+struct Generating {
+    active: String,
+    path: Vec<usize>,
+    idx: usize,
+    quick_click: bool,
+    mods: gdk::ModifierType,
+    at: (f64, f64),
+    entries: mpsc::Receiver<Vec<(String, String)>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Fade {
+    start: Instant,
+    from: f64,
+    to: f64,
+}
+
+// A `confirm` action clicked once: the item it belongs to, by path and index,
+// and which of the item's actions it is.
+#[derive(Debug)]
+struct Armed {
+    path: Vec<usize>,
+    idx: usize,
+    cmd: String,
+    at: Instant,
+}
+
+// Changes to the window itself, applied by `update` once the state is free.
+#[derive(Debug)]
+enum WindowOp {
+    // Map on this output, then anchor according to `spawn`
+    Show(Option<gdk::Monitor>),
+    Hide,
+    Keyboard(KeyboardMode),
+}
+
+// Submenu transition: the center glides from `from` to (cx, cy) while the
+// ring scales from `scale_from` to 1.
+#[derive(Clone, Copy, Debug)]
+struct NavAnim {
+    start: Instant,
+    from: (f64, f64),
+    scale_from: f64,
+}
+
+// Fraction of an animation of `ms` milliseconds elapsed since `start`.
+fn progress(start: Instant, ms: u64) -> f64 {
+    if ms == 0 {
+        return 1.0;
+    }
+    (start.elapsed().as_secs_f64() * 1000.0 / ms as f64).min(1.0)
+}
+
+impl State {
+    // Center and ring scale to draw with. Hit-testing always uses the final
+    // (cx, cy), so clicks are never blocked by a running transition.
+    fn nav_frame(&self) -> (f64, f64, f64) {
+        let Some(nav) = self.nav else {
+            return (self.cx, self.cy, 1.0);
+        };
+        let t = progress(nav.start, self.settings.submenu_anim_ms);
+        let e = 1.0 - (1.0 - t).powi(3);
+        (
+            nav.from.0 + (self.cx - nav.from.0) * e,
+            nav.from.1 + (self.cy - nav.from.1) * e,
+            nav.scale_from + (1.0 - nav.scale_from) * e,
+        )
+    }
+
+    fn menu(&self) -> &Menu {
+        &self.view
+    }
+
+    fn compositor(&self) -> Compositor {
+        self.settings
+            .compositor
+            .unwrap_or_else(actions::detect_compositor)
+    }
+
+    fn theme(&self) -> &Theme {
+        match self.settings.theme.unwrap_or(self.system_scheme) {
+            ColorScheme::Dark => &self.settings.dark_theme,
+            ColorScheme::Light => &self.settings.light_theme,
+        }
+    }
+
+    fn items(&self) -> &[MenuItem] {
+        &current_menu(self.menu(), &self.path).items
+    }
+
+    // One-line summary for `waydo status`.
+    fn status(&self) -> String {
+        format!(
+            "visible={} depth={} menu={}",
+            self.visible,
+            self.path.len(),
+            current_menu(self.menu(), &self.path).name
+        )
+    }
+
+    // Layout of the menu currently shown: its own, or the global default.
+    fn layout(&self) -> MenuLayout {
+        current_menu(self.menu(), &self.path)
+            .layout
+            .unwrap_or(self.settings.layout)
+    }
 }
 
-fn current_items(path: &[usize]) -> &'static [MenuItem] {
-    let mut items = ROOT_MENU;
+// Labels of the submenu items leading from the root to the current menu,
+// not counting "More" items between pages of the same menu.
+fn breadcrumb<'a>(root: &'a Menu, path: &[usize]) -> Vec<&'a str> {
+    let mut labels = Vec::with_capacity(path.len());
+    let mut menu = root;
     for &idx in path {
-        if idx >= items.len() {
+        let Some(item) = menu.items.get(idx) else {
+            break;
+        };
+        let ItemKind::Submenu { menu: sub, .. } = &item.kind else {
             break;
+        };
+        if sub.page == 0 {
+            labels.push(item.label.as_str());
+        }
+        menu = sub;
+    }
+    labels
+}
+
+// Whether some item of `menu` or its submenus has a `show_when`, so opening
+// it needs the compositor's word on the focused window.
+fn conditional(menu: &Menu) -> bool {
+    menu.items.iter().any(|item| {
+        item.show_when.is_some()
+            || matches!(&item.kind, ItemKind::Submenu { menu, .. } if conditional(menu))
+    })
+}
+
+// Rebuilds the shown menu from the active one, filtered for the focused
+// window the menu was opened over.
+fn refresh_view(st: &mut State) {
+    fn filter(menu: &Menu, ctx: &actions::WindowContext) -> Menu {
+        let items = menu
+            .items
+            .iter()
+            .filter(|item| item.show_when.is_none_or(|c| condition_holds(c, ctx)))
+            .map(|item| {
+                let mut item = item.clone();
+                if let ItemKind::Submenu { menu, .. } = &mut item.kind {
+                    *menu = filter(menu, ctx);
+                }
+                item
+            })
+            .collect();
+        Menu {
+            items,
+            ..menu.clone()
+        }
+    }
+
+    // Reorders each menu for `frequent`; `labels` leads from the root to it.
+    fn promote(menu: &mut Menu, st: &State, labels: &mut Vec<String>) {
+        for item in &mut menu.items {
+            if let ItemKind::Submenu { menu, .. } = &mut item.kind {
+                labels.push(item.label.clone());
+                promote(menu, st, labels);
+                labels.pop();
+            }
+        }
+        let path: Vec<&str> = labels.iter().map(String::as_str).collect();
+        let counts: Vec<u64> = menu
+            .items
+            .iter()
+            .map(|item| st.usage.count(&usage::key(&st.active, &path, &item.label)))
+            .collect();
+        let mut items: Vec<Option<MenuItem>> = menu.items.drain(..).map(Some).collect();
+        menu.items = usage::frequent_first(&counts, st.settings.frequent)
+            .into_iter()
+            .filter_map(|i| items[i].take())
+            .collect();
+    }
+
+    let Some(menu) = st.menus.get(&st.active) else {
+        st.view = Menu::default();
+        return;
+    };
+    let mut view = if conditional(menu) {
+        filter(menu, &st.window)
+    } else {
+        menu.clone()
+    };
+    if st.settings.frequent > 0 {
+        promote(&mut view, st, &mut Vec::new());
+    }
+    paginate(&mut view, st.settings.max_items);
+    st.view = view;
+
+    // The open submenu may be gone from the rebuilt view; fall back to the
+    // deepest level still there.
+    let depth = valid_depth(&st.view, &st.path);
+    if depth < st.path.len() {
+        log::debug!("path {:?} cut to depth {}", st.path, depth);
+        st.path.truncate(depth);
+        st.highlight = None;
+    }
+    if st.highlight.is_some_and(|i| i >= st.items().len()) {
+        st.highlight = None;
+    }
+}
+
+// Splits menus longer than `max` into pages: the first `max - 1` items and a
+// "More" submenu holding the rest, split the same way.
+fn paginate(menu: &mut Menu, max: usize) {
+    if max < 2 {
+        return;
+    }
+    let mut more = None;
+    if menu.items.len() > max {
+        let mut next = Menu {
+            name: menu.name.clone(),
+            items: menu.items.split_off(max - 1),
+            layout: menu.layout,
+            sticky: menu.sticky,
+            page: menu.page + 1,
+        };
+        paginate(&mut next, max);
+        more = Some(next);
+    }
+    for item in &mut menu.items {
+        if let ItemKind::Submenu { menu, .. } = &mut item.kind {
+            paginate(menu, max);
+        }
+    }
+    if let Some(next) = more {
+        menu.items.push(MenuItem {
+            label: "More".to_string(),
+            kind: ItemKind::Submenu {
+                menu: next,
+                on_click: None,
+                quick_click: false,
+            },
+            color: None,
+            text_color: None,
+            font_size: None,
+            icon: None,
+            image: None,
+            enabled: true,
+            show_when: None,
+            description: None,
+            shift_action: None,
+            ctrl_action: None,
+        });
+    }
+}
+
+fn condition_holds(c: Condition, ctx: &actions::WindowContext) -> bool {
+    let holds = match c.state {
+        WindowState::Focused => ctx.focused,
+        WindowState::Floating => ctx.focused && ctx.floating,
+        WindowState::Tiling => ctx.focused && !ctx.floating,
+        WindowState::Fullscreen => ctx.focused && ctx.fullscreen,
+    };
+    holds != c.negate
+}
+
+fn current_menu<'a>(root: &'a Menu, path: &[usize]) -> &'a Menu {
+    let mut menu = root;
+    for &idx in &path[..valid_depth(root, path)] {
+        if let ItemKind::Submenu { menu: sub, .. } = &menu.items[idx].kind {
+            menu = sub;
         }
-        match items[idx].kind {
-            ItemKind::Submenu { items: sub, .. } => items = sub,
-            ItemKind::Action(_) => break,
+    }
+    menu
+}
+
+// Length of the longest prefix of `path` that leads through submenus of
+// `root`. Anything past it no longer exists, e.g. after a reload shrank a menu.
+fn valid_depth(root: &Menu, path: &[usize]) -> usize {
+    let mut menu = root;
+    for (depth, &idx) in path.iter().enumerate() {
+        match menu.items.get(idx).map(|item| &item.kind) {
+            Some(ItemKind::Submenu { menu: sub, .. }) => menu = sub,
+            _ => return depth,
         }
     }
-    items
+    path.len()
 }
 
 fn dist2(ax: f64, ay: f64, bx: f64, by: f64) -> f64 {
@@ -562,138 +485,171 @@ fn dist2(ax: f64, ay: f64, bx: f64, by: f64) -> f64 {
     dx * dx + dy * dy
 }
 
-fn key_token_to_evdev(tok: &str) -> Option<u16> {
-    match tok {
-        "ctrl" => Some(29),
-        "shift" => Some(42),
-        "alt" => Some(56),
-        "meta" | "super" => Some(125),
-        "1" => Some(2),
-        "2" => Some(3),
-        "3" => Some(4),
-        "4" => Some(5),
-        "5" => Some(6),
-        "6" => Some(7),
-        "7" => Some(8),
-        "8" => Some(9),
-        "9" => Some(10),
-        "0" => Some(11),
-        "f1" => Some(59),
-        "f2" => Some(60),
-        "f3" => Some(61),
-        "f4" => Some(62),
-        "f5" => Some(63),
-        "f6" => Some(64),
-        "f7" => Some(65),
-        "f8" => Some(66),
-        "f9" => Some(67),
-        "f10" => Some(68),
-        "f11" => Some(87),
-        "f12" => Some(88),
-        "a" => Some(30),
-        "b" => Some(48),
-        "c" => Some(46),
-        "d" => Some(32),
-        "e" => Some(18),
-        "f" => Some(33),
-        "g" => Some(34),
-        "h" => Some(35),
-        "i" => Some(23),
-        "j" => Some(36),
-        "k" => Some(37),
-        "l" => Some(38),
-        "m" => Some(50),
-        "n" => Some(49),
-        "o" => Some(24),
-        "p" => Some(25),
-        "q" => Some(16),
-        "r" => Some(19),
-        "s" => Some(31),
-        "t" => Some(20),
-        "u" => Some(22),
-        "v" => Some(47),
-        "w" => Some(17),
-        "x" => Some(45),
-        "y" => Some(21),
-        "z" => Some(44),
-        "minus" => Some(12),
-        "equal" | "plus" => Some(13),
-        "delete" | "backspace" => Some(14),
-        "pageup" => Some(104),
-        "pagedown" => Some(109),
-        _ => None,
-    }
-}
-
-fn run_ydotool_sequence(spec: &str) {
-    for combo in spec.split_whitespace() {
-        run_ydotool_combo(combo);
-        // Small spacing helps tools/apps register successive synthetic keys reliably.
-        std::thread::sleep(std::time::Duration::from_millis(20));
-    }
-}
-
-fn run_ydotool_combo(spec: &str) {
-    let parts: Vec<&str> = spec.split('-').collect();
-    if parts.is_empty() {
-        return;
+fn run_action(action: Action, st: &mut State) {
+    if let Some(sound) = action.sound.as_ref().or(st.settings.sound.as_ref()) {
+        actions::play_sound(sound);
     }
 
-    let (mods, main) = parts.split_at(parts.len() - 1);
-    let main_code = match key_token_to_evdev(main[0]) {
-        Some(c) => c,
-        None => return,
-    };
-
-    let mut args: Vec<String> = vec!["key".to_string()];
-    let mut mod_codes: Vec<u16> = Vec::new();
+    // `waydo-` commands act on the menu itself instead of being run.
+    if let Some(cmd) = action.cmd.strip_prefix("waydo-") {
+        run_internal(cmd, st);
+        if action.close_on_click {
+            hide_menu(st);
+        }
+        return;
+    }
 
-    for m in mods {
-        let code = match key_token_to_evdev(m) {
-            Some(c) => c,
-            None => return,
-        };
-        mod_codes.push(code);
-        args.push(format!("{code}:1"));
+    if action.close_on_click {
+        hide_menu(st);
     }
 
-    args.push(format!("{main_code}:1"));
-    args.push(format!("{main_code}:0"));
+    let delay_before_ms = action.delay_before_ms;
+    let job = actions::Job {
+        cmd: action.cmd,
+        compositor: st.compositor(),
+        key_delay: Duration::from_millis(action.key_delay_ms.unwrap_or(st.settings.key_delay_ms)),
+        cwd: action.cwd,
+        env: action.env,
+    };
+    if delay_before_ms > 0 {
+        // Counted from when the overlay has faded out and disappeared.
+        let delay = delay_before_ms
+            + if action.close_on_click {
+                st.settings.fade_ms
+            } else {
+                0
+            };
+        glib::timeout_add_local_once(Duration::from_millis(delay), move || {
+            actions::dispatch(job);
+        });
+    } else {
+        actions::dispatch(job);
+    }
+}
 
-    for code in mod_codes.iter().rev() {
-        args.push(format!("{code}:0"));
+// Called once the highlight has moved; leaving items is silent.
+fn hover_feedback(st: &State) {
+    if st.highlight.is_some()
+        && let Some(sound) = &st.settings.hover_sound
+    {
+        actions::play_sound(sound);
     }
+}
 
-    let _ = Command::new("ydotool").args(&args).status();
+fn run_internal(cmd: &str, st: &mut State) {
+    let release = match parse_internal(cmd) {
+        Ok(Some(grab)) => !grab,
+        Ok(None) => !st.keyboard_released,
+        Err(e) => {
+            eprintln!("waydo: {}", e);
+            return;
+        }
+    };
+    set_keyboard_released(st, release);
 }
 
-fn run_niri_action(action: &str) {
-    if let Some(spec) = action.strip_prefix("key-") {
-        run_ydotool_sequence(spec);
-        return;
+// `waydo-keyboard on|off|toggle`: whether the menu should grab the keyboard,
+// or None to flip it.
+fn parse_internal(cmd: &str) -> Result<Option<bool>, String> {
+    match cmd.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["keyboard", "toggle"] => Ok(None),
+        ["keyboard", "on"] => Ok(Some(true)),
+        ["keyboard", "off"] => Ok(Some(false)),
+        _ => Err(format!("unknown action `waydo-{cmd}`")),
     }
+}
 
-    let mut cmd = Command::new("niri");
-    cmd.arg("msg").arg("action");
-    for part in action.split_whitespace() {
-        cmd.arg(part);
+// `waydo check`: reports every action in the config that can't run as
+// written, and returns the exit status.
+// The config as the daemon and `waydo check` take it: parsed, and checked
+// against what the compositor in use can report.
+fn load_config() -> Result<config::Config, String> {
+    let config = config::load_config()?;
+    let compositor = config
+        .settings
+        .compositor
+        .unwrap_or_else(actions::detect_compositor);
+    if compositor != Compositor::Niri {
+        return Ok(config);
+    }
+    let mut names: Vec<&String> = config.menus.keys().collect();
+    names.sort();
+    for name in names {
+        for item in &config.menus[name].items {
+            if let Some(Condition {
+                state: WindowState::Fullscreen,
+                negate,
+            }) = item.show_when
+            {
+                return Err(format!(
+                    "menu `{}` item `{}`: niri doesn't report fullscreen windows, so \
+                     `show_when = \"{}fullscreen\"` can't be used with it",
+                    name,
+                    item.label,
+                    if negate { "!" } else { "" }
+                ));
+            }
+        }
     }
-    let _ = cmd.status();
+    Ok(config)
 }
 
-fn run_action(action: Action, st: &mut State, win: &ApplicationWindow, da: &DrawingArea) {
-    if action.close_on_click {
-        hide_menu(st, win, da);
+fn check_config() -> i32 {
+    let config = match load_config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("waydo: invalid menu config: {}", e);
+            return 1;
+        }
+    };
+
+    // Submenus are checked as the menus they are, not under each parent.
+    let mut names: Vec<&String> = config.menus.keys().collect();
+    names.sort();
+    let mut problems = 0;
+    for name in names {
+        for item in &config.menus[name].items {
+            let actions = match &item.kind {
+                ItemKind::Action(action) => std::slice::from_ref(action),
+                ItemKind::Submenu {
+                    on_click: Some(action),
+                    ..
+                } => std::slice::from_ref(action),
+                ItemKind::Cycle { actions, .. } => actions,
+                _ => continue,
+            };
+            for action in actions {
+                let checked = match action.cmd.strip_prefix("waydo-") {
+                    Some(cmd) => parse_internal(cmd).map(drop),
+                    None => actions::check(&action.cmd),
+                };
+                if let Err(e) = checked {
+                    println!("menu `{}` item `{}`: {}", name, item.label, e);
+                    problems += 1;
+                }
+            }
+        }
     }
+    if problems > 0 {
+        println!("{problems} problem(s) found");
+        return 1;
+    }
+    println!("ok");
+    0
+}
 
-    if action.cmd.starts_with("screenshot") {
-        let action_owned = action.cmd.to_string();
-        glib::timeout_add_local_once(std::time::Duration::from_millis(80), move || {
-            run_niri_action(&action_owned);
-        });
-    } else {
-        run_niri_action(action.cmd);
+// Without the grab, keys go to the focused app while the menu stays usable
+// with the pointer.
+fn set_keyboard_released(st: &mut State, release: bool) {
+    if release == st.keyboard_released {
+        return;
     }
+    st.keyboard_released = release;
+    st.pending.push(WindowOp::Keyboard(if release {
+        KeyboardMode::None
+    } else {
+        KeyboardMode::OnDemand
+    }));
 }
 
 fn install_transparent_css() {
@@ -713,6 +669,17 @@ fn install_transparent_css() {
     );
 }
 
+// Distance from the center to the item ring. Grows past the configured base
+// once `n` circles would otherwise come closer than `item_gap` to each other.
+fn ring_distance(g: &Geometry, n: usize) -> f64 {
+    if n < 2 {
+        return g.ring_distance;
+    }
+    let chord = 2.0 * g.item_radius + g.item_gap;
+    let needed = chord / (2.0 * (std::f64::consts::PI / n as f64).sin());
+    g.ring_distance.max(needed)
+}
+
 fn ring_layout(n: usize, cx: f64, cy: f64, dist: f64) -> Vec<(f64, f64)> {
     if n == 0 {
         return Vec::new();
@@ -726,6 +693,47 @@ fn ring_layout(n: usize, cx: f64, cy: f64, dist: f64) -> Vec<(f64, f64)> {
         .collect()
 }
 
+// Rows of square cells, as close to square overall as possible, centered
+// horizontally below the center circle.
+fn grid_layout(n: usize, cx: f64, cy: f64, g: &Geometry) -> Vec<(f64, f64)> {
+    if n == 0 {
+        return Vec::new();
+    }
+    let cols = (n as f64).sqrt().ceil() as usize;
+    let cell = grid_cell(g);
+    let left = cx - (cols - 1) as f64 * cell / 2.0;
+    let top = cy + g.center_radius + g.item_gap + g.item_radius;
+    (0..n)
+        .map(|i| {
+            let (row, col) = (i / cols, i % cols);
+            (left + col as f64 * cell, top + row as f64 * cell)
+        })
+        .collect()
+}
+
+fn grid_cell(g: &Geometry) -> f64 {
+    2.0 * g.item_radius + g.item_gap
+}
+
+// Item centers for the current menu around (cx, cy).
+fn item_points(st: &State, cx: f64, cy: f64) -> Vec<(f64, f64)> {
+    let n = st.items().len();
+    let g = &st.settings.geometry;
+    match st.layout() {
+        MenuLayout::Grid => grid_layout(n, cx, cy, g),
+        MenuLayout::Ring | MenuLayout::Wedge => {
+            let (sin, cos) = st.rotation.sin_cos();
+            ring_layout(n, cx, cy, ring_distance(g, n))
+                .into_iter()
+                .map(|(x, y)| {
+                    let (dx, dy) = (x - cx, y - cy);
+                    (cx + dx * cos - dy * sin, cy + dx * sin + dy * cos)
+                })
+                .collect()
+        }
+    }
+}
+
 fn closest_index_for_pointer(
     px: f64,
     py: f64,
@@ -733,9 +741,10 @@ fn closest_index_for_pointer(
     cy: f64,
     points: &[(f64, f64)],
     deadzone: f64,
+    outer: f64,
 ) -> Option<usize> {
     let pointer_r2 = dist2(px, py, cx, cy);
-    if pointer_r2 < deadzone * deadzone {
+    if pointer_r2 < deadzone * deadzone || pointer_r2 > outer * outer {
         return None;
     }
 
@@ -751,8 +760,78 @@ fn closest_index_for_pointer(
     best.map(|(i, _)| i)
 }
 
-fn draw_ui(cr: &gtk::cairo::Context, _w: i32, _h: i32, st: &State) {
-    if !st.anchored || !st.visible {
+// Item under (x, y), using the same geometry draw_ui lays the menu out with.
+// None inside the center deadzone, past the outer one, or outside every
+// cell of a grid. With `dismiss_outside` only the item's own shape counts.
+// Blank slots and disabled items are never selected, not even their
+// neighbours.
+fn item_at(st: &State, x: f64, y: f64) -> Option<usize> {
+    slot_at(st, x, y).filter(|&i| st.items()[i].selectable())
+}
+
+// Index of the layout slot under (x, y), whatever the item in it.
+fn slot_at(st: &State, x: f64, y: f64) -> Option<usize> {
+    let g = &st.settings.geometry;
+    let strict = st.settings.dismiss_outside;
+    let points = item_points(st, st.cx, st.cy);
+    let ring_outer = ring_distance(g, points.len()) + g.item_radius;
+    match st.layout() {
+        MenuLayout::Grid => {
+            let half = if strict {
+                g.item_radius
+            } else {
+                grid_cell(g) / 2.0
+            };
+            points
+                .iter()
+                .position(|&(bx, by)| (x - bx).abs() <= half && (y - by).abs() <= half)
+        }
+        MenuLayout::Ring if strict => {
+            let r2 = g.item_radius * g.item_radius;
+            points
+                .iter()
+                .position(|&(bx, by)| dist2(x, y, bx, by) <= r2)
+        }
+        MenuLayout::Wedge if strict => {
+            closest_index_for_pointer(x, y, st.cx, st.cy, &points, g.center_radius, ring_outer)
+        }
+        MenuLayout::Ring | MenuLayout::Wedge => {
+            let outer = ring_outer + g.deadzone_outer;
+            closest_index_for_pointer(x, y, st.cx, st.cy, &points, g.deadzone, outer)
+        }
+    }
+}
+
+// Gesture mode: true when the pointer has just moved out across the ring of
+// item centers. Re-arms once it comes back inside.
+fn gesture_crossed(st: &mut State, x: f64, y: f64) -> bool {
+    if !st.settings.gesture || st.layout() == MenuLayout::Grid {
+        return false;
+    }
+    let dist = ring_distance(&st.settings.geometry, st.items().len());
+    let outside = dist2(x, y, st.cx, st.cy) >= dist * dist;
+    if !outside {
+        st.gesture_armed = true;
+        return false;
+    }
+    std::mem::replace(&mut st.gesture_armed, false)
+}
+
+// Whether (x, y) is on the root anchor's close button. The center takes
+// precedence where the two overlap, e.g. after keyboard navigation.
+fn on_anchor(st: &State, x: f64, y: f64) -> bool {
+    let center_r = st.settings.geometry.center_radius;
+    let r = st.theme().anchor_radius;
+    !st.path.is_empty()
+        && r > 0.0
+        && dist2(x, y, st.root_cx, st.root_cy) <= r * r
+        && dist2(x, y, st.cx, st.cy) > center_r * center_r
+}
+
+fn draw_ui(cr: &gtk::cairo::Context, w: i32, h: i32, st: &State) {
+    // Before its first allocation the area can be 0x0; there's nothing to
+    // draw into until it has a real size.
+    if w <= 0 || h <= 0 || !st.anchored || st.opacity <= 0.0 {
         return;
     }
 
@@ -761,115 +840,1971 @@ fn draw_ui(cr: &gtk::cairo::Context, _w: i32, _h: i32, st: &State) {
     let _ = cr.paint();
     cr.set_operator(gtk::cairo::Operator::Over);
 
-    let cx = st.cx;
-    let cy = st.cy;
+    // Draw into a group so the fade applies to the menu as a whole.
+    cr.push_group();
+    draw_menu(cr, st);
+    let _ = cr.pop_group_to_source();
+    let _ = cr.paint_with_alpha(st.opacity);
+}
 
-    if !st.path.is_empty() {
-        cr.set_source_rgba(1.0, 1.0, 1.0, 0.18);
-        cr.arc(st.root_cx, st.root_cy, 6.0, 0.0, std::f64::consts::TAU);
+fn draw_menu(cr: &gtk::cairo::Context, st: &State) {
+    let (cx, cy, scale) = st.nav_frame();
+
+    let shadow = st.settings.shadow;
+    let theme = st.theme();
+
+    if !st.path.is_empty() && theme.anchor_radius > 0.0 {
+        let (rx, ry, r) = (st.root_cx, st.root_cy, theme.anchor_radius);
+        if shadow {
+            draw_shadow(cr, rx, ry, r);
+        }
+        set_color(cr, theme.anchor);
+        cr.arc(rx, ry, r, 0.0, std::f64::consts::TAU);
         let _ = cr.fill();
+
+        // Clicking the anchor closes the menu from any depth.
+        let k = r * 0.4;
+        cr.set_line_width(1.5);
+        set_color(
+            cr,
+            Color {
+                a: (theme.anchor.a * 3.6).min(1.0),
+                ..theme.anchor
+            },
+        );
+        cr.move_to(rx - k, ry - k);
+        cr.line_to(rx + k, ry + k);
+        cr.move_to(rx + k, ry - k);
+        cr.line_to(rx - k, ry + k);
+        let _ = cr.stroke();
     }
 
-    let center_r = CENTER_RADIUS;
-    if st.path.is_empty() {
-        cr.set_source_rgba(0.75, 0.2, 0.2, 0.88);
+    let g = &st.settings.geometry;
+    let center_r = g.center_radius;
+    if shadow {
+        draw_shadow(cr, cx, cy, center_r);
+    }
+    // A menu reached through `open` items goes back rather than closing.
+    let at_root = st.path.is_empty() && st.chain.is_empty();
+    if at_root {
+        set_color(cr, theme.center);
     } else {
-        cr.set_source_rgba(0.22, 0.48, 0.82, 0.92);
+        set_color(cr, theme.center_submenu);
     }
     cr.arc(cx, cy, center_r, 0.0, std::f64::consts::TAU);
     let _ = cr.fill();
 
-    cr.set_line_width(2.0);
-    cr.set_source_rgba(1.0, 1.0, 1.0, 0.76);
+    cr.set_line_width(theme.stroke_width);
+    set_color(cr, theme.stroke);
     cr.arc(cx, cy, center_r, 0.0, std::f64::consts::TAU);
     let _ = cr.stroke();
 
-    cr.set_line_width(2.5);
-    cr.set_source_rgba(1.0, 1.0, 1.0, 0.95);
-    if st.path.is_empty() {
-        cr.move_to(cx - 7.0, cy - 7.0);
-        cr.line_to(cx + 7.0, cy + 7.0);
-        cr.move_to(cx + 7.0, cy - 7.0);
-        cr.line_to(cx - 7.0, cy + 7.0);
+    if let Some(left) = debounce_left(st) {
+        draw_countdown(cr, cx, cy, center_r + 4.0, left, theme.highlight);
+    }
+
+    let glyph_icon = if at_root {
+        &theme.close_icon
+    } else {
+        &theme.back_icon
+    };
+    if let Some(icon) = glyph_icon.as_ref().and_then(|name| st.icons.get(name)) {
+        draw_icon(cr, icon, cx, cy, center_r * 1.2);
     } else {
-        cr.move_to(cx + 5.0, cy - 8.0);
-        cr.line_to(cx - 5.0, cy);
-        cr.line_to(cx + 5.0, cy + 8.0);
+        cr.set_line_width(2.5);
+        set_color(cr, theme.glyph);
+        if at_root {
+            cr.move_to(cx - 7.0, cy - 7.0);
+            cr.line_to(cx + 7.0, cy + 7.0);
+            cr.move_to(cx + 7.0, cy - 7.0);
+            cr.line_to(cx - 7.0, cy + 7.0);
+        } else {
+            cr.move_to(cx + 5.0, cy - 8.0);
+            cr.line_to(cx - 5.0, cy);
+            cr.line_to(cx + 5.0, cy + 8.0);
+        }
+        let _ = cr.stroke();
     }
-    let _ = cr.stroke();
 
-    let items = current_items(&st.path);
+    // Pin on the center's rim while the menu is sticky.
+    if current_menu(st.menu(), &st.path).sticky {
+        let (px, py) = (cx + center_r * 0.7, cy - center_r * 0.7);
+        cr.set_line_width(2.0);
+        set_color(cr, theme.glyph);
+        cr.move_to(px, py);
+        cr.line_to(px - 6.0, py + 6.0);
+        let _ = cr.stroke();
+        set_color(cr, theme.center_submenu);
+        cr.arc(px, py, 4.5, 0.0, std::f64::consts::TAU);
+        let _ = cr.fill_preserve();
+        set_color(cr, theme.glyph);
+        let _ = cr.stroke();
+    }
+
+    let items = st.items();
     let n = items.len();
+    let layout = st.layout();
+    let points = item_points(st, cx, cy);
+
+    if !st.warnings.is_empty() {
+        cr.set_source_rgba(1.0, 0.78, 0.35, 0.95);
+        let text = text_layout(cr, &st.warnings.join("\n"), g.font_size - 2.0);
+        let (_, h) = text.pixel_size();
+        let bottom = match layout {
+            MenuLayout::Grid => points.iter().fold(cy, |b, &(_, by)| b.max(by)),
+            MenuLayout::Ring | MenuLayout::Wedge => cy + ring_distance(g, n),
+        };
+        let top = bottom + g.item_radius + 12.0;
+        show_centered(cr, &text, cx, top + h as f64 / 2.0);
+    }
+
+    if !st.path.is_empty() {
+        cr.set_source_rgba(1.0, 1.0, 1.0, 0.85);
+        let crumbs = breadcrumb(st.menu(), &st.path).join("  ›  ");
+        let text = text_layout(cr, &crumbs, g.font_size - 1.0);
+        let (_, h) = text.pixel_size();
+        let top = match layout {
+            MenuLayout::Grid => cy - g.center_radius,
+            MenuLayout::Ring | MenuLayout::Wedge => cy - ring_distance(g, n) - g.item_radius,
+        };
+        let bottom = top - 12.0;
+        show_centered(cr, &text, cx, bottom - h as f64 / 2.0);
+    }
+
     if n == 0 {
         return;
     }
 
-    let dist = ITEM_RING_DISTANCE;
-    let radius = ITEM_RADIUS;
+    let _ = cr.save();
+    cr.translate(cx, cy);
+    cr.scale(scale, scale);
+    cr.translate(-cx, -cy);
+
+    if st.settings.spokes {
+        cr.set_line_width(1.0);
+        set_color(
+            cr,
+            Color {
+                a: theme.stroke.a * 0.3,
+                ..theme.stroke
+            },
+        );
+        for (item, &(bx, by)) in items.iter().zip(&points) {
+            let d = dist2(bx, by, cx, cy).sqrt();
+            if matches!(item.kind, ItemKind::Blank) || d <= center_r {
+                continue;
+            }
+            // From the center's edge; the item covers the other end.
+            let k = center_r / d;
+            cr.move_to(cx + (bx - cx) * k, cy + (by - cy) * k);
+            cr.line_to(bx, by);
+        }
+        let _ = cr.stroke();
+    }
 
-    let points = ring_layout(n, cx, cy, dist);
+    if st.settings.sector
+        && layout != MenuLayout::Grid
+        && let Some(&(bx, by)) = st.highlight.and_then(|i| points.get(i))
+    {
+        // The highlighted item's share of the full turn, pointing at it.
+        let step = std::f64::consts::TAU / n as f64;
+        let a = (by - cy).atan2(bx - cx);
+        let outer = ring_distance(g, n) + g.item_radius;
+        cr.new_path();
+        cr.arc(cx, cy, outer, a - step / 2.0, a + step / 2.0);
+        cr.arc_negative(cx, cy, center_r, a + step / 2.0, a - step / 2.0);
+        cr.close_path();
+        set_color(
+            cr,
+            Color {
+                a: theme.highlight.a * 0.18,
+                ..theme.highlight
+            },
+        );
+        let _ = cr.fill();
+    }
 
+    let crumbs = breadcrumb(st.menu(), &st.path);
     for i in 0..n {
         let (bx, by) = points[i];
-        let item = items[i];
-        cr.set_source_rgba(item.color.r, item.color.g, item.color.b, item.color.a);
-        cr.arc(bx, by, radius, 0.0, std::f64::consts::TAU);
-        let _ = cr.fill();
-
-        cr.set_line_width(2.0);
-        cr.set_source_rgba(1.0, 1.0, 1.0, 0.70);
-        cr.arc(bx, by, radius, 0.0, std::f64::consts::TAU);
-        let _ = cr.stroke();
+        let item = &items[i];
+        if let ItemKind::Blank = item.kind {
+            cr.set_line_width(1.5);
+            cr.set_source_rgba(1.0, 1.0, 1.0, 0.18);
+            item_path(cr, layout, g, n, (cx, cy), (bx, by));
+            let _ = cr.stroke();
+            continue;
+        }
+        let grow = st.grow.get(i).copied().unwrap_or(1.0);
+        let _ = cr.save();
+        cr.translate(bx, by);
+        cr.scale(grow, grow);
+        cr.translate(-bx, -by);
+        // Disabled items are drawn as usual, then composited faded out.
+        if !item.enabled {
+            cr.push_group();
+        }
+        if shadow {
+            if layout == MenuLayout::Ring {
+                draw_shadow(cr, bx, by, g.item_radius);
+            } else {
+                // Shapes other than circles get a plain offset copy instead.
+                let _ = cr.save();
+                cr.translate(0.0, SHADOW_OFFSET);
+                cr.set_source_rgba(0.0, 0.0, 0.0, 0.28);
+                item_path(cr, layout, g, n, (cx, cy), (bx, by));
+                let _ = cr.fill();
+                let _ = cr.restore();
+            }
+        }
+        let fill = theme.fill(item);
+        if st.settings.gradient {
+            // Lit from slightly above the middle, darkening towards the edge.
+            let r = g.item_radius;
+            let gradient =
+                gtk::cairo::RadialGradient::new(bx, by - r * 0.3, r * 0.1, bx, by, r * 1.1);
+            for (offset, c) in [(0.0, fill.shade(0.25)), (1.0, fill.shade(-0.3))] {
+                gradient.add_color_stop_rgba(offset, c.r, c.g, c.b, c.a);
+            }
+            let _ = cr.set_source(&gradient);
+        } else {
+            set_color(cr, fill);
+        }
+        item_path(cr, layout, g, n, (cx, cy), (bx, by));
+        let _ = cr.fill();
+
+        let image = item.image.as_ref().and_then(|path| st.images.get(path));
+        if let Some(image) = image {
+            // Covers the item, cropped to its shape.
+            let (iw, ih) = (image.width() as f64, image.height() as f64);
+            let k = 2.0 * g.item_radius / iw.min(ih);
+            let _ = cr.save();
+            item_path(cr, layout, g, n, (cx, cy), (bx, by));
+            cr.clip();
+            cr.translate(bx, by);
+            cr.scale(k, k);
+            cr.set_source_pixbuf(image, -iw / 2.0, -ih / 2.0);
+            let _ = cr.paint();
+            let _ = cr.restore();
+        }
+
+        if st.highlight == Some(i) {
+            cr.set_line_width(theme.stroke_width + 1.5);
+            set_color(cr, theme.highlight);
+        } else {
+            cr.set_line_width(theme.stroke_width);
+            set_color(cr, theme.stroke);
+        }
+        item_path(cr, layout, g, n, (cx, cy), (bx, by));
+        let _ = cr.stroke();
+
+        if let Some(armed) = st
+            .armed
+            .as_ref()
+            .filter(|a| a.idx == i && a.path == st.path)
+        {
+            let pulse = (armed.at.elapsed().as_secs_f64() * std::f64::consts::TAU * 2.0).cos();
+            cr.set_line_width(theme.stroke_width + 2.5);
+            cr.set_source_rgba(0.92, 0.22, 0.2, 0.65 + 0.35 * pulse);
+            item_path(cr, layout, g, n, (cx, cy), (bx, by));
+            let _ = cr.stroke();
+
+            let left = 1.0 - armed.at.elapsed().as_secs_f64() / CONFIRM_WINDOW.as_secs_f64();
+            let r = g.item_radius + theme.stroke_width + 4.0;
+            draw_countdown(cr, bx, by, r, left, theme.highlight);
+        }
+
+        if image.is_some() {
+            // The image stands in for the label.
+        } else if let Some(icon) = item.icon.as_ref().and_then(|name| st.icons.get(name)) {
+            // The pixbuf is in device pixels; draw it at its logical size.
+            let s = st.scale_factor.max(1) as f64;
+            draw_icon(cr, icon, bx, by, icon.width().max(icon.height()) as f64 / s);
+        } else {
+            set_color(cr, label_color(item, fill, theme));
+            let size = item.font_size.map_or(g.font_size, |size| size * g.scale);
+            let layout = text_layout(cr, shown_label(st, &crumbs, item), size);
+            fit_label(
+                &layout,
+                2.0 * g.item_radius - LABEL_PADDING,
+                st.settings.label_overflow,
+            );
+            show_centered(cr, &layout, bx, by);
+        }
+
+        if st.settings.show_shortcuts && i < 9 && item.selectable() {
+            let r = g.item_radius * std::f64::consts::FRAC_1_SQRT_2;
+            draw_badge(cr, &(i + 1).to_string(), bx + r, by - r, g.font_size * 0.6);
+        }
+
+        if !item.enabled {
+            let _ = cr.pop_group_to_source();
+            let _ = cr.paint_with_alpha(DISABLED_ALPHA);
+        }
+        let _ = cr.restore();
+    }
+
+    let _ = cr.restore();
+
+    if st.settings.tooltips
+        && let Some(text) = st.highlight.and_then(|i| items.get(i)).and_then(tooltip)
+    {
+        draw_tooltip(cr, text, cx, cy - g.center_radius - 8.0, g.font_size - 2.0);
+    }
+}
+
+// A `cycle` item's label is the one of the action it ran last.
+fn shown_label<'a>(st: &'a State, crumbs: &[&str], item: &'a MenuItem) -> &'a str {
+    if let ItemKind::Cycle { labels, .. } = &item.kind
+        && let Some(label) = st
+            .cycles
+            .get(&usage::key(&st.active, crumbs, &item.label))
+            .and_then(|&i| labels.get(i))
+    {
+        return label;
+    }
+    &item.label
+}
+
+// What a hovered item does: its description, or else the raw command.
+fn tooltip(item: &MenuItem) -> Option<&str> {
+    if let Some(description) = &item.description {
+        return Some(description);
+    }
+    match &item.kind {
+        ItemKind::Action(action) => Some(&action.cmd),
+        ItemKind::Submenu { on_click, .. } => on_click.as_ref().map(|a| a.cmd.as_str()),
+        ItemKind::Generated(generator) => Some(&generator.cmd),
+        ItemKind::OpenMenu { name } => Some(name),
+        ItemKind::Cycle { .. } => None,
+        ItemKind::Blank => None,
+    }
+}
+
+// One line of text on a dark pill whose bottom edge sits at `bottom`.
+fn draw_tooltip(cr: &gtk::cairo::Context, text: &str, x: f64, bottom: f64, size: f64) {
+    let layout = text_layout(cr, text, size);
+    layout.set_ellipsize(pango::EllipsizeMode::Middle);
+    layout.set_width(TOOLTIP_MAX_WIDTH as i32 * pango::SCALE);
+    let (_, logical) = layout.pixel_extents();
+    let (w, h) = (logical.width() as f64 + 12.0, logical.height() as f64 + 6.0);
+    let (x0, y0, r) = (x - w / 2.0, bottom - h, h / 2.0);
+
+    let q = std::f64::consts::FRAC_PI_2;
+    cr.new_path();
+    cr.arc(x0 + w - r, y0 + r, r, -q, q);
+    cr.arc(x0 + r, y0 + r, r, q, 3.0 * q);
+    cr.close_path();
+    cr.set_source_rgba(0.08, 0.08, 0.08, 0.88);
+    let _ = cr.fill();
+
+    cr.set_source_rgba(1.0, 1.0, 1.0, 0.95);
+    show_centered(cr, &layout, x, bottom - h / 2.0);
+}
+
+// Thin arc starting at the top and running clockwise over the `left`
+// fraction of a full turn, for time running out.
+fn draw_countdown(cr: &gtk::cairo::Context, x: f64, y: f64, r: f64, left: f64, color: Color) {
+    if left <= 0.0 {
+        return;
+    }
+    let start = -std::f64::consts::FRAC_PI_2;
+    cr.new_path();
+    cr.arc(
+        x,
+        y,
+        r,
+        start,
+        start + std::f64::consts::TAU * left.min(1.0),
+    );
+    cr.set_line_width(2.0);
+    set_color(cr, color);
+    let _ = cr.stroke();
+}
+
+// Fraction of the `toggle_debounce_ms` window after the last toggle still to
+// go, while toggles are being ignored.
+fn debounce_left(st: &State) -> Option<f64> {
+    let window = Duration::from_millis(st.settings.toggle_debounce_ms);
+    let elapsed = st.last_toggle?.elapsed();
+    (elapsed < window).then(|| 1.0 - elapsed.as_secs_f64() / window.as_secs_f64())
+}
+
+// Small dark disc with `text` centered on (x, y), for `show_shortcuts`.
+fn draw_badge(cr: &gtk::cairo::Context, text: &str, x: f64, y: f64, size: f64) {
+    cr.new_path();
+    cr.arc(x, y, size * 0.9, 0.0, std::f64::consts::TAU);
+    cr.set_source_rgba(0.08, 0.08, 0.08, 0.88);
+    let _ = cr.fill();
+    cr.set_source_rgba(1.0, 1.0, 1.0, 0.95);
+    show_centered(cr, &text_layout(cr, text, size), x, y);
+}
+
+fn set_color(cr: &gtk::cairo::Context, c: Color) {
+    cr.set_source_rgba(c.r, c.g, c.b, c.a);
+}
+
+// The item's own text color, the theme's, or black/white depending on how
+// bright its fill is, so labels stay readable on light brush colors.
+fn label_color(item: &MenuItem, fill: Color, theme: &Theme) -> Color {
+    if let Some(c) = item.text_color.or(theme.text) {
+        return c;
+    }
+    if fill.luminance() > 0.55 {
+        Color {
+            r: 0.0,
+            g: 0.0,
+            b: 0.0,
+            a: 0.90,
+        }
+    } else {
+        Color {
+            r: 1.0,
+            g: 1.0,
+            b: 1.0,
+            a: 0.95,
+        }
+    }
+}
+
+// Soft dark disc slightly larger than, and offset below, a circle of radius
+// `r` at (x, y), faded out with a radial gradient.
+fn draw_shadow(cr: &gtk::cairo::Context, x: f64, y: f64, r: f64) {
+    let (sy, spread) = (y + SHADOW_OFFSET, SHADOW_SPREAD.min(r));
+    let gradient = gtk::cairo::RadialGradient::new(x, sy, r - spread, x, sy, r + spread);
+    gradient.add_color_stop_rgba(0.0, 0.0, 0.0, 0.0, 0.35);
+    gradient.add_color_stop_rgba(1.0, 0.0, 0.0, 0.0, 0.0);
+    let _ = cr.set_source(&gradient);
+    cr.new_path();
+    cr.arc(x, sy, r + spread, 0.0, std::f64::consts::TAU);
+    let _ = cr.fill();
+}
+
+// Draws `icon` centered on (x, y), its longer side `size` logical pixels.
+fn draw_icon(cr: &gtk::cairo::Context, icon: &Pixbuf, x: f64, y: f64, size: f64) {
+    let (iw, ih) = (icon.width() as f64, icon.height() as f64);
+    let k = size / iw.max(ih);
+    let _ = cr.save();
+    cr.translate(x, y);
+    cr.scale(k, k);
+    cr.set_source_pixbuf(icon, -iw / 2.0, -ih / 2.0);
+    let _ = cr.paint();
+    let _ = cr.restore();
+}
+
+fn reload_icons(st: &mut State) {
+    let size = st.settings.geometry.item_radius as i32;
+    let s = &st.settings;
+    let glyphs = [&s.dark_theme, &s.light_theme]
+        .into_iter()
+        .flat_map(|t| [&t.close_icon, &t.back_icon])
+        .flatten()
+        .map(String::as_str);
+    st.icons = load_icons(&st.menus, glyphs, size, st.scale_factor.max(1));
+    st.images = load_images(&st.menus, 2 * size, st.scale_factor.max(1));
+}
+
+// Decodes every item `image` once, so hover animations only repaint them.
+fn load_images(menus: &HashMap<String, Menu>, size: i32, scale: i32) -> HashMap<PathBuf, Pixbuf> {
+    fn collect<'a>(menu: &'a Menu, paths: &mut Vec<&'a Path>) {
+        for item in &menu.items {
+            if let Some(image) = &item.image {
+                paths.push(image);
+            }
+            if let ItemKind::Submenu { menu, .. } = &item.kind {
+                collect(menu, paths);
+            }
+        }
+    }
+
+    let mut paths = Vec::new();
+    for menu in menus.values() {
+        collect(menu, &mut paths);
+    }
+    paths.sort_unstable();
+    paths.dedup();
+
+    let px = size * scale;
+    let mut images = HashMap::new();
+    for path in paths {
+        match Pixbuf::from_file_at_scale(path, px, px, true) {
+            Ok(pixbuf) => {
+                images.insert(path.to_path_buf(), pixbuf);
+            }
+            Err(e) => eprintln!(
+                "waydo: image {} not loaded, using its label: {}",
+                path.display(),
+                e
+            ),
+        }
+    }
+    images
+}
+
+// Resolves every icon name in the menus to a pixbuf sized to fit an item,
+// `size` logical pixels at `scale` device pixels each. Names the theme
+// doesn't know are reported once and drawn as labels.
+fn load_icons<'a>(
+    menus: &'a HashMap<String, Menu>,
+    extra: impl Iterator<Item = &'a str>,
+    size: i32,
+    scale: i32,
+) -> HashMap<String, Pixbuf> {
+    fn collect<'a>(menu: &'a Menu, names: &mut Vec<&'a str>) {
+        for item in &menu.items {
+            if let Some(icon) = &item.icon {
+                names.push(icon);
+            }
+            if let ItemKind::Submenu { menu, .. } = &item.kind {
+                collect(menu, names);
+            }
+        }
+    }
+
+    let mut names: Vec<&str> = extra.collect();
+    for menu in menus.values() {
+        collect(menu, &mut names);
+    }
+    names.sort_unstable();
+    names.dedup();
+
+    let mut icons = HashMap::new();
+    let Some(display) = gdk::Display::default() else {
+        return icons;
+    };
+    let theme = gtk::IconTheme::for_display(&display);
+
+    for name in names {
+        let path = theme
+            .has_icon(name)
+            .then(|| {
+                theme
+                    .lookup_icon(
+                        name,
+                        &[],
+                        size,
+                        scale,
+                        gtk::TextDirection::None,
+                        gtk::IconLookupFlags::empty(),
+                    )
+                    .file()?
+                    .path()
+            })
+            .flatten();
+        let px = size * scale;
+        match path.map(|p| Pixbuf::from_file_at_scale(p, px, px, true)) {
+            Some(Ok(pixbuf)) => {
+                icons.insert(name.to_string(), pixbuf);
+            }
+            _ => eprintln!("waydo: icon `{}` not found, using its label", name),
+        }
+    }
+    icons
+}
+
+// Outline of item `i` of `n`: a circle around its ring point, the item's
+// angular slice of the annulus around the center for wedges, or a rounded
+// square for grid cells.
+fn item_path(
+    cr: &gtk::cairo::Context,
+    layout: MenuLayout,
+    g: &Geometry,
+    n: usize,
+    (cx, cy): (f64, f64),
+    (bx, by): (f64, f64),
+) {
+    cr.new_path();
+    match layout {
+        MenuLayout::Ring => cr.arc(bx, by, g.item_radius, 0.0, std::f64::consts::TAU),
+        MenuLayout::Wedge => {
+            // Centered on the item's direction, which follows the rotation.
+            let step = std::f64::consts::TAU / n as f64;
+            let a0 = (by - cy).atan2(bx - cx) - 0.5 * step;
+            let a1 = a0 + step;
+            let inner = g.center_radius + 4.0;
+            let outer = ring_distance(g, n) + g.item_radius;
+            cr.arc(cx, cy, outer, a0, a1);
+            cr.arc_negative(cx, cy, inner, a1, a0);
+            cr.close_path();
+        }
+        MenuLayout::Grid => {
+            let (r, c) = (g.item_radius, g.item_radius / 4.0);
+            let (x0, y0, x1, y1) = (bx - r, by - r, bx + r, by + r);
+            let q = std::f64::consts::FRAC_PI_2;
+            cr.arc(x1 - c, y0 + c, c, -q, 0.0);
+            cr.arc(x1 - c, y1 - c, c, 0.0, q);
+            cr.arc(x0 + c, y1 - c, c, q, 2.0 * q);
+            cr.arc(x0 + c, y0 + c, c, 2.0 * q, 3.0 * q);
+            cr.close_path();
+        }
+    }
+}
+
+fn text_layout(cr: &gtk::cairo::Context, text: &str, size: f64) -> pango::Layout {
+    let mut font = pango::FontDescription::from_string(FONT_FAMILY);
+    font.set_absolute_size(size * pango::SCALE as f64);
+
+    let layout = pangocairo::functions::create_layout(cr);
+    layout.set_font_description(Some(&font));
+    layout.set_alignment(pango::Alignment::Center);
+    layout.set_text(text);
+    layout
+}
+
+// Constrains `layout` to `width` pixels. show_centered uses the logical
+// extents, so a wrapped label stays vertically centered as a block.
+fn fit_label(layout: &pango::Layout, width: f64, overflow: LabelOverflow) {
+    layout.set_width((width.max(1.0) * pango::SCALE as f64) as i32);
+    layout.set_ellipsize(pango::EllipsizeMode::End);
+    if overflow == LabelOverflow::Wrap {
+        layout.set_wrap(pango::WrapMode::WordChar);
+        // Negative heights limit the number of lines.
+        layout.set_height(-2);
+    }
+}
+
+// Draws `layout` with its logical extents centered on (x, y).
+fn show_centered(cr: &gtk::cairo::Context, layout: &pango::Layout, x: f64, y: f64) {
+    let (_, logical) = layout.pixel_extents();
+    cr.move_to(
+        x - logical.x() as f64 - logical.width() as f64 / 2.0,
+        y - logical.y() as f64 - logical.height() as f64 / 2.0,
+    );
+    pangocairo::functions::show_layout(cr, layout);
+}
+
+fn activate_item(
+    st: &mut State,
+    idx: usize,
+    quick_click: bool,
+    mods: gdk::ModifierType,
+    (x, y): (f64, f64),
+    da: &DrawingArea,
+) {
+    // Whatever this click is, it isn't confirming anything armed before it
+    // unless it runs the very same action.
+    let armed = st.armed.take();
+    let Some(item) = st.items().get(idx).filter(|item| item.selectable()) else {
+        return;
+    };
+
+    // A modifier held while clicking picks the item's alternative action,
+    // where it has one; Shift wins over Ctrl.
+    let alternative = if mods.contains(gdk::ModifierType::SHIFT_MASK) {
+        item.shift_action.as_ref()
+    } else if mods.contains(gdk::ModifierType::CONTROL_MASK) {
+        item.ctrl_action.as_ref()
+    } else {
+        None
+    };
+    if let Some(action) = alternative.cloned() {
+        if confirmed(st, armed, idx, &action, da) {
+            run_action(action, st);
+        }
+        return;
+    }
+
+    if let ItemKind::Generated(generator) = &item.kind {
+        // Opened by `poll_generator` when the command finishes.
+        st.generating = Some(Generating {
+            active: st.active.clone(),
+            path: st.path.clone(),
+            idx,
+            quick_click,
+            mods,
+            at: (x, y),
+            entries: actions::generate(generator),
+        });
+        return;
+    }
+
+    let sticky = current_menu(st.menu(), &st.path).sticky;
+    let Some(item) = st.items().get(idx) else {
+        return;
+    };
+    // The next page of a long menu opens where this one is.
+    let in_place = sticky || matches!(&item.kind, ItemKind::Submenu { menu, .. } if menu.page > 0);
+    match &item.kind {
+        ItemKind::Action(action) => {
+            let mut action = action.clone();
+            action.close_on_click &= !sticky;
+            let label = item.label.clone();
+            if !confirmed(st, armed, idx, &action, da) {
+                return;
+            }
+            if st.settings.frequent > 0 {
+                let key = usage::key(&st.active, &breadcrumb(st.menu(), &st.path), &label);
+                st.usage.bump(key);
+            }
+            run_action(action, st);
+        }
+        ItemKind::Cycle { actions, .. } => {
+            let key = usage::key(&st.active, &breadcrumb(st.menu(), &st.path), &item.label);
+            let next = st.cycles.get(&key).map_or(0, |i| i + 1) % actions.len();
+            let mut action = actions[next].clone();
+            // Clicked through in place, like a sticky menu.
+            action.close_on_click = false;
+            st.cycles.insert(key, next);
+            run_action(action, st);
+            da.queue_draw();
+        }
+        ItemKind::OpenMenu { name } => {
+            let name = name.clone();
+            let previous = std::mem::take(&mut st.active);
+            st.chain.push(previous);
+            switch_root(st, name, true, da);
+        }
+        ItemKind::Blank | ItemKind::Generated(_) => {}
+        ItemKind::Submenu {
+            on_click,
+            quick_click: allow_quick,
+            ..
+        } => {
+            if let Some(mut action) = on_click.clone() {
+                if quick_click && *allow_quick {
+                    action.close_on_click = true;
+                    run_action(action, st);
+                    return;
+                }
+                run_action(action, st);
+            }
+            let from = (st.cx, st.cy);
+            st.path.push(idx);
+            st.highlight = None;
+            if !in_place {
+                (st.cx, st.cy) = on_screen(st, x, y);
+            }
+            start_nav(st, from, true);
+            da.queue_draw();
+        }
+    }
+}
+
+// Turns a finished generator into a submenu in place of its item and opens
+// it, unless the menu has moved on since. Filled in once per showing; the
+// view is rebuilt on the next open.
+fn poll_generator(st: &mut State, da: &DrawingArea) {
+    let Some(pending) = st.generating.take() else {
+        return;
+    };
+    let entries = match pending.entries.try_recv() {
+        Ok(entries) => entries,
+        Err(mpsc::TryRecvError::Empty) => {
+            st.generating = Some(pending);
+            return;
+        }
+        Err(mpsc::TryRecvError::Disconnected) => Vec::new(),
+    };
+    if !st.visible || st.active != pending.active || st.path != pending.path {
+        return;
+    }
+    let Some(item) = st.items().get(pending.idx) else {
+        return;
+    };
+    let ItemKind::Generated(generator) = &item.kind else {
+        return;
+    };
+    let mut menu = generated_menu(&item.label, generator, entries);
+    paginate(&mut menu, st.settings.max_items);
+    let mut parent = &mut st.view;
+    for &i in &st.path {
+        match &mut parent.items[i].kind {
+            ItemKind::Submenu { menu, .. } => parent = menu,
+            _ => return,
+        }
+    }
+    parent.items[pending.idx].kind = ItemKind::Submenu {
+        menu,
+        on_click: None,
+        quick_click: false,
+    };
+    activate_item(
+        st,
+        pending.idx,
+        pending.quick_click,
+        pending.mods,
+        pending.at,
+        da,
+    );
+}
+
+fn generated_menu(label: &str, generator: &Generator, entries: Vec<(String, String)>) -> Menu {
+    let mut items: Vec<MenuItem> = entries
+        .into_iter()
+        .map(|(label, cmd)| MenuItem {
+            label,
+            kind: ItemKind::Action(Action {
+                cmd,
+                close_on_click: generator.close_on_click,
+                key_delay_ms: None,
+                sound: None,
+                confirm: false,
+                delay_before_ms: 0,
+                cwd: None,
+                env: Vec::new(),
+            }),
+            color: None,
+            text_color: None,
+            font_size: None,
+            icon: None,
+            image: None,
+            enabled: true,
+            show_when: None,
+            description: None,
+            shift_action: None,
+            ctrl_action: None,
+        })
+        .collect();
+    // A lone disabled placeholder rather than an empty ring.
+    if items.is_empty() {
+        items.push(MenuItem {
+            label: "none".to_string(),
+            kind: ItemKind::Action(Action {
+                cmd: String::new(),
+                close_on_click: false,
+                key_delay_ms: None,
+                sound: None,
+                confirm: false,
+                delay_before_ms: 0,
+                cwd: None,
+                env: Vec::new(),
+            }),
+            color: None,
+            text_color: None,
+            font_size: None,
+            icon: None,
+            image: None,
+            enabled: false,
+            show_when: None,
+            description: None,
+            shift_action: None,
+            ctrl_action: None,
+        });
+    }
+    Menu {
+        name: label.to_string(),
+        items,
+        layout: None,
+        sticky: false,
+        page: 0,
+    }
+}
+
+// A `confirm` action only runs when it was armed by the previous click, not
+// too long ago; otherwise this click arms it.
+fn confirmed(
+    st: &mut State,
+    armed: Option<Armed>,
+    idx: usize,
+    action: &Action,
+    da: &DrawingArea,
+) -> bool {
+    if !action.confirm {
+        return true;
+    }
+    let again = armed.is_some_and(|a| {
+        a.path == st.path && a.idx == idx && a.cmd == action.cmd && a.at.elapsed() < CONFIRM_WINDOW
+    });
+    if !again {
+        log::debug!("armed `{}`", action.cmd);
+        st.armed = Some(Armed {
+            path: st.path.clone(),
+            idx,
+            cmd: action.cmd.clone(),
+            at: Instant::now(),
+        });
+        da.queue_draw();
+    }
+    again
+}
+
+// What a primary click or a lifted touch at (x, y) does once the menu is
+// anchored: close on the anchor dot, go back on the center, else select.
+fn select_at(st: &mut State, x: f64, y: f64, mods: gdk::ModifierType, da: &DrawingArea) {
+    // Clicks anywhere but on the armed item disarm it.
+    let armed = st.armed.take();
+    let g = st.settings.geometry;
+    let center_r = g.center_radius;
+    if on_anchor(st, x, y) {
+        hide_menu(st);
+        return;
+    }
+    if dist2(x, y, st.cx, st.cy) <= center_r * center_r {
+        go_back(st, x, y, da);
+        return;
+    }
+
+    let slot = slot_at(st, x, y);
+    log::debug!("nearest slot {:?} in `{}`", slot, st.menu().name);
+    let Some(idx) = slot else {
+        if st.settings.dismiss_outside {
+            hide_menu(st);
+        }
+        return;
+    };
+    if !st.items()[idx].selectable() {
+        return;
+    }
+
+    // Clicks between the deadzone and `quick_click_radius` of the
+    // ring are quick clicks: a submenu item's `on_click` runs and
+    // the menu closes. Further out the submenu opens. Grid cells
+    // have no inner region, so they always descend.
+    let dist = ring_distance(&g, st.items().len());
+    let quick_r = dist * st.settings.quick_click_radius;
+    let quick_click =
+        st.layout() != MenuLayout::Grid && dist2(x, y, st.cx, st.cy) <= quick_r * quick_r;
+
+    st.armed = armed;
+    activate_item(st, idx, quick_click, mods, (x, y), da);
+}
+
+// What pointer motion over the surface calls for.
+#[derive(Debug, PartialEq)]
+enum Moved {
+    Nothing,
+    Redraw,
+    // A gesture stroke crossed the ring over this item.
+    Crossed(usize),
+}
+
+// Anchors a menu still waiting for motion, or follows the pointer over an
+// anchored one.
+fn pointer_moved(st: &mut State, x: f64, y: f64) -> Moved {
+    if !st.visible {
+        return Moved::Nothing;
+    }
+    if !st.anchored {
+        if st.mapped_at.is_some_and(|t| t.elapsed() < ANCHOR_GRACE) {
+            st.early_pointer = Some((x, y));
+            return Moved::Nothing;
+        }
+        anchor_at(st, x, y);
+        return Moved::Redraw;
+    }
+
+    st.px = x;
+    st.py = y;
+    let hit = item_at(st, x, y);
+    if let Some(idx) = hit
+        && gesture_crossed(st, x, y)
+    {
+        return Moved::Crossed(idx);
+    }
+    if hit == st.highlight {
+        return Moved::Nothing;
+    }
+    st.highlight = hit;
+    hover_feedback(st);
+    Moved::Redraw
+}
+
+// With `spawn = "pointer"` the menu anchors where the pointer enters the
+// freshly mapped surface, which is where it is now. Returns whether the menu
+// needs redrawing.
+fn pointer_entered(st: &mut State, x: f64, y: f64) -> bool {
+    if !st.visible || st.anchored || st.settings.spawn != Spawn::Pointer {
+        return false;
+    }
+    anchor_at(st, x, y);
+    true
+}
+
+// With `selection = "release-drag"` a primary press starts a flick. It
+// anchors a menu still waiting for the pointer; one already anchored by
+// motion, the grace timer or `spawn` takes a press inside its deadzone the
+// same way, while presses elsewhere stay plain clicks. Returns whether the
+// menu needs redrawing.
+fn pressed_at(st: &mut State, button: u32, x: f64, y: f64) -> bool {
+    st.pressed_at = Some(Instant::now());
+    st.press_flick = false;
+    st.press_anchored = false;
+    if st.settings.selection != Selection::ReleaseDrag
+        || button != gdk::BUTTON_PRIMARY
+        || !st.visible
+    {
+        return false;
+    }
+    if st.anchored {
+        let deadzone = st.settings.geometry.deadzone;
+        st.press_flick = dist2(x, y, st.cx, st.cy) <= deadzone * deadzone;
+        return false;
+    }
+    anchor_at(st, x, y);
+    st.press_flick = true;
+    st.press_anchored = true;
+    true
+}
+
+// What letting go of a mouse button calls for.
+#[derive(Debug, PartialEq)]
+enum Release {
+    Nothing,
+    Back,
+    Hide,
+    Anchor,
+    Activate(usize),
+    Select,
+}
+
+fn released_at(st: &mut State, button: u32, x: f64, y: f64) -> Release {
+    let held = st.pressed_at.take().map(|t| t.elapsed());
+    let press_flick = std::mem::take(&mut st.press_flick);
+    let press_anchored = std::mem::take(&mut st.press_anchored);
+    if !st.visible {
+        return Release::Nothing;
+    }
+
+    log::debug!(
+        "button {} released at ({:.1}, {:.1}), center ({:.1}, {:.1})",
+        button,
+        x,
+        y,
+        st.cx,
+        st.cy
+    );
+    match button {
+        gdk::BUTTON_PRIMARY => {}
+        gdk::BUTTON_SECONDARY if st.anchored => return Release::Back,
+        gdk::BUTTON_SECONDARY => return Release::Hide,
+        _ => return Release::Nothing,
+    }
+    if !st.anchored {
+        return Release::Anchor;
+    }
+
+    // The flick from the press ends on whatever the pointer is over. Let go
+    // short of the ring, the menu the press anchored stays open for a click,
+    // and on one that was already open it was a click.
+    if press_flick && let Some(idx) = st.highlight {
+        return Release::Activate(idx);
+    }
+    if press_anchored {
+        return Release::Nothing;
+    }
+
+    // Held on the center, the click closes the whole menu instead of going
+    // back one level.
+    let (center_r, long) = (
+        st.settings.geometry.center_radius,
+        Duration::from_millis(st.settings.long_press_ms),
+    );
+    if !long.is_zero()
+        && held.is_some_and(|held| held >= long)
+        && dist2(x, y, st.cx, st.cy) <= center_r * center_r
+    {
+        return Release::Hide;
+    }
+    Release::Select
+}
+
+// Shows the named menu in place of the current one, centered on the root
+// anchor as if it had been opened there.
+fn switch_root(st: &mut State, name: String, entering: bool, da: &DrawingArea) {
+    st.active = name;
+    st.path.clear();
+    st.highlight = None;
+    refresh_view(st);
+    let from = (st.cx, st.cy);
+    (st.cx, st.cy) = on_screen(st, st.root_cx, st.root_cy);
+    start_nav(st, from, entering);
+    da.queue_draw();
+}
+
+// Pops one submenu level and recenters at (x, y); closes the menu at root.
+fn go_back(st: &mut State, x: f64, y: f64, da: &DrawingArea) {
+    let from_page = current_menu(st.menu(), &st.path).page > 0;
+    if st.path.pop().is_none() {
+        match st.chain.pop() {
+            Some(previous) => switch_root(st, previous, false, da),
+            None => hide_menu(st),
+        }
+        return;
+    }
+    let from = (st.cx, st.cy);
+    st.highlight = None;
+    if !from_page && !current_menu(st.menu(), &st.path).sticky {
+        (st.cx, st.cy) = on_screen(st, x, y);
+    }
+    start_nav(st, from, false);
+    da.queue_draw();
+}
+
+// First selectable item stepping `step` (mod len) at a time from `start`,
+// not counting `start` itself unless it's the only one.
+fn next_selectable(items: &[MenuItem], start: usize, step: usize) -> Option<usize> {
+    let n = items.len();
+    (1..=n)
+        .map(|k| (start + k * step) % n)
+        .find(|&i| items[i].selectable())
+}
+
+fn handle_key(
+    st: &mut State,
+    key: gdk::Key,
+    mods: gdk::ModifierType,
+    da: &DrawingArea,
+) -> glib::Propagation {
+    if !st.visible {
+        return glib::Propagation::Proceed;
+    }
+    // Only Return on the armed item confirms it.
+    let armed = st.armed.take();
+
+    // Escape always closes, from any depth and even before the menu is anchored.
+    if key == gdk::Key::Escape {
+        hide_menu(st);
+        return glib::Propagation::Stop;
+    }
+
+    if !st.settings.keyboard {
+        return glib::Propagation::Proceed;
+    }
+
+    if !st.anchored {
+        // Keyboard-only use: place the menu in the middle of the surface.
+        anchor_at(st, da.width() as f64 / 2.0, da.height() as f64 / 2.0);
+    }
+
+    // 1-9 activate the item in that slot, just as clicking it would.
+    if let Some(idx) = shortcut_index(key) {
+        if st.items().get(idx).is_some_and(MenuItem::selectable) {
+            let at = item_points(st, st.cx, st.cy)[idx];
+            st.armed = armed;
+            activate_item(st, idx, false, mods, at, da);
+        }
+        da.queue_draw();
+        return glib::Propagation::Stop;
+    }
+
+    let n = st.items().len();
+    match key {
+        gdk::Key::Right | gdk::Key::Down | gdk::Key::l | gdk::Key::j if n > 0 => {
+            let start = st.highlight.map_or(n - 1, |i| i);
+            st.highlight = next_selectable(st.items(), start, 1);
+        }
+        gdk::Key::Left | gdk::Key::Up | gdk::Key::h | gdk::Key::k if n > 0 => {
+            let start = st.highlight.unwrap_or(0);
+            st.highlight = next_selectable(st.items(), start, n - 1);
+        }
+        gdk::Key::Return | gdk::Key::KP_Enter => {
+            if let Some(idx) = st.highlight {
+                let (cx, cy) = (st.cx, st.cy);
+                st.armed = armed;
+                activate_item(st, idx, false, mods, (cx, cy), da);
+            }
+        }
+        gdk::Key::BackSpace => {
+            if st.path.pop().is_some() {
+                st.highlight = None;
+                let from = (st.cx, st.cy);
+                start_nav(st, from, false);
+            } else if let Some(previous) = st.chain.pop() {
+                switch_root(st, previous, false, da);
+            }
+        }
+        _ => return glib::Propagation::Proceed,
+    }
+
+    da.queue_draw();
+    glib::Propagation::Stop
+}
+
+// Slot a digit key stands for: 1 is the first item, on the main row or the
+// keypad.
+fn shortcut_index(key: gdk::Key) -> Option<usize> {
+    let digit = key.to_unicode()?.to_digit(10)?;
+    (1..=9).contains(&digit).then(|| digit as usize - 1)
+}
+
+fn start_fade(st: &mut State, to: f64) {
+    st.fade = Some(Fade {
+        start: Instant::now(),
+        from: st.opacity,
+        to,
+    });
+}
+
+// Input is ignored as soon as the menu stops being visible; the surface
+// itself only goes away once the fade-out has finished.
+fn hide_menu(st: &mut State) {
+    st.visible = false;
+    st.opening = None;
+    set_keyboard_released(st, false);
+    actions::release_held();
+    if st.settings.fade_ms == 0 || !st.anchored {
+        finish_hide(st);
+    } else {
+        start_fade(st, 0.0);
+    }
+}
+
+fn finish_hide(st: &mut State) {
+    st.opacity = 0.0;
+    st.fade = None;
+    st.nav = None;
+    st.anchored = false;
+    st.touch_anchored = false;
+    st.highlight = None;
+    st.rotation = 0.0;
+    st.grow.clear();
+    st.path.clear();
+    st.armed = None;
+    st.generating = None;
+    st.pending.push(WindowOp::Hide);
+}
+
+// Entering a submenu grows the new ring in; going back shrinks it down
+// from slightly larger, so the two directions read differently.
+fn start_nav(st: &mut State, from: (f64, f64), entering: bool) {
+    st.grow.clear();
+    st.armed = None;
+    st.rotation = 0.0;
+    if st.settings.submenu_anim_ms == 0 {
+        return;
+    }
+    st.nav = Some(NavAnim {
+        start: Instant::now(),
+        from,
+        scale_from: if entering { 0.6 } else { 1.3 },
+    });
+}
+
+fn tick_animations(st: &mut State, da: &DrawingArea) {
+    if let Some(fade) = st.fade {
+        let t = progress(fade.start, st.settings.fade_ms);
+        st.opacity = fade.from + (fade.to - fade.from) * t;
+        if t >= 1.0 {
+            st.fade = None;
+            if !st.visible {
+                finish_hide(st);
+            }
+        }
+        da.queue_draw();
+    }
 
-        cr.set_source_rgba(1.0, 1.0, 1.0, 0.95);
-        cr.select_font_face(
-            "Sans",
-            gtk::cairo::FontSlant::Normal,
-            gtk::cairo::FontWeight::Normal,
-        );
-        cr.set_font_size(FONT_SIZE);
+    if let Some(nav) = st.nav {
+        if progress(nav.start, st.settings.submenu_anim_ms) >= 1.0 {
+            st.nav = None;
+        }
+        da.queue_draw();
+    }
 
-        let text = items[i].label;
-        if let Ok(ext) = cr.text_extents(text) {
-            cr.move_to(
-                bx - ext.width() / 2.0 - ext.x_bearing(),
-                by + ext.height() / 2.0,
-            );
-            let _ = cr.show_text(text);
+    if st.visible && tick_grow(st) {
+        da.queue_draw();
+    }
+
+    // Armed items pulse until confirmed or timed out.
+    if let Some(armed) = &st.armed {
+        if armed.at.elapsed() >= CONFIRM_WINDOW {
+            st.armed = None;
         }
+        da.queue_draw();
+    }
+    if st.visible && debounce_left(st).is_some() {
+        da.queue_draw();
     }
+    poll_generator(st, da);
+    poll_opening(st, da);
+
+    update_input_region(st, da);
+    publish_status(st);
 }
 
-fn hide_menu(st: &mut State, win: &ApplicationWindow, _da: &DrawingArea) {
-    st.visible = false;
-    st.anchored = false;
-    st.path.clear();
-    win.hide();
+// Mirrors `waydo status` into `status_file` whenever it changes. The file is
+// replaced by a rename so a bar polling it never reads half a line.
+fn publish_status(st: &mut State) {
+    let Some(path) = &st.settings.status_file else {
+        return;
+    };
+    let status = st.status();
+    if status == st.published {
+        return;
+    }
+    let tmp = path.with_extension("tmp");
+    let written = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(&tmp, format!("{status}\n")))
+        .and_then(|()| fs::rename(&tmp, path));
+    if let Err(e) = written {
+        eprintln!("waydo: failed to write {}: {}", path.display(), e);
+    }
+    st.published = status;
+}
+
+// With `click_through`, an anchored menu only takes input within a margin of
+// `deadzone_outer` around it (and on the anchor dot); everywhere else clicks
+// go to the windows below. Otherwise, and until the menu is anchored, the
+// whole surface does.
+fn update_input_region(st: &mut State, da: &DrawingArea) {
+    let rects = if st.settings.click_through && st.visible && st.anchored {
+        menu_bounds(st)
+    } else {
+        Vec::new()
+    };
+    if rects == st.input_region {
+        return;
+    }
+    let Some(surface) = da.native().and_then(|n| n.surface()) else {
+        return;
+    };
+    let region = if rects.is_empty() {
+        // Larger than any output.
+        let all = gtk::cairo::RectangleInt::new(0, 0, i32::MAX / 2, i32::MAX / 2);
+        gtk::cairo::Region::create_rectangle(&all)
+    } else {
+        let rects: Vec<_> = rects
+            .iter()
+            .map(|&(x, y, w, h)| gtk::cairo::RectangleInt::new(x, y, w, h))
+            .collect();
+        gtk::cairo::Region::create_rectangles(&rects)
+    };
+    log::debug!("input region {:?}", rects);
+    surface.set_input_region(&region);
+    st.input_region = rects;
+}
+
+// Bounding boxes of the menu shown and, in submenus, of the anchor dot.
+fn menu_bounds(st: &State) -> Vec<(i32, i32, i32, i32)> {
+    let g = &st.settings.geometry;
+    let pad = g.item_radius * st.settings.hover_scale.max(1.0) + g.deadzone_outer;
+    let (mut x0, mut y0) = (st.cx - g.center_radius, st.cy - g.center_radius);
+    let (mut x1, mut y1) = (st.cx + g.center_radius, st.cy + g.center_radius);
+    for (x, y) in item_points(st, st.cx, st.cy) {
+        x0 = x0.min(x - pad);
+        y0 = y0.min(y - pad);
+        x1 = x1.max(x + pad);
+        y1 = y1.max(y + pad);
+    }
+    let rect = |x0: f64, y0: f64, x1: f64, y1: f64| {
+        let (x, y) = (x0.floor() as i32, y0.floor() as i32);
+        (x, y, x1.ceil() as i32 - x, y1.ceil() as i32 - y)
+    };
+    let mut rects = vec![rect(x0, y0, x1, y1)];
+    let r = st.theme().anchor_radius;
+    if !st.path.is_empty() && r > 0.0 {
+        rects.push(rect(
+            st.root_cx - r,
+            st.root_cy - r,
+            st.root_cx + r,
+            st.root_cy + r,
+        ));
+    }
+    rects
+}
+
+// Steps every item's size one tick towards its target. Returns whether any
+// of them changed.
+fn tick_grow(st: &mut State) -> bool {
+    let (target, ms) = (st.settings.hover_scale, st.settings.hover_ms);
+    let step = if ms == 0 {
+        f64::INFINITY
+    } else {
+        (target - 1.0).abs() * TICK_MS as f64 / ms as f64
+    };
+    let n = st.items().len();
+    let highlight = st.highlight;
+    st.grow.resize(n, 1.0);
+    let mut changed = false;
+    for (i, s) in st.grow.iter_mut().enumerate() {
+        let to = if highlight == Some(i) { target } else { 1.0 };
+        if *s != to {
+            *s = if (to - *s).abs() <= step {
+                to
+            } else {
+                *s + step.copysign(to - *s)
+            };
+            changed = true;
+        }
+    }
+    changed
+}
+
+fn is_touch(gesture: &impl IsA<gtk::Gesture>) -> bool {
+    gesture
+        .device()
+        .is_some_and(|d| d.source() == gdk::InputSource::Touchscreen)
+}
+
+fn anchor_at(st: &mut State, x: f64, y: f64) {
+    log::debug!("anchored at ({:.1}, {:.1})", x, y);
+    if !st.anchored && st.settings.fade_ms > 0 {
+        start_fade(st, 1.0);
+    }
+    st.anchored = true;
+    st.px = x;
+    st.py = y;
+    st.root_cx = x;
+    st.root_cy = y;
+    st.last_root = Some((x, y));
+    (st.cx, st.cy) = on_screen(st, x, y);
+}
+
+// Where to center the current menu for a center at (x, y): with
+// `keep_on_screen`, moved just enough for all of it to fit on the surface.
+// A menu bigger than the surface is centered on it instead.
+fn on_screen(st: &State, x: f64, y: f64) -> (f64, f64) {
+    if !st.settings.keep_on_screen {
+        return (x, y);
+    }
+    fit_on_surface(x, y, menu_extent(st), st.surface)
+}
+
+// Moves a center at (x, y) so `extent` around it fits a `w`x`h` surface.
+// Without a size yet, i.e. before the first allocation, it stays put.
+fn fit_on_surface(
+    x: f64,
+    y: f64,
+    (x0, y0, x1, y1): (f64, f64, f64, f64),
+    (w, h): (f64, f64),
+) -> (f64, f64) {
+    if !(w > 0.0 && h > 0.0) {
+        return (x, y);
+    }
+    let fit = |v: f64, lo: f64, hi: f64, size: f64| {
+        if hi - lo >= size {
+            (size - hi - lo) / 2.0
+        } else {
+            v.clamp(-lo, size - hi)
+        }
+    };
+    (fit(x, x0, x1, w), fit(y, y0, y1, h))
+}
+
+// Bounds of the current menu relative to its center: the center circle and
+// the whole ring, or the grid's cells below the center.
+fn menu_extent(st: &State) -> (f64, f64, f64, f64) {
+    let g = &st.settings.geometry;
+    let (mut x0, mut y0, mut x1, mut y1) = match st.layout() {
+        MenuLayout::Ring | MenuLayout::Wedge => {
+            let r = ring_distance(g, st.items().len()) + g.item_radius;
+            (-r, -r, r, r)
+        }
+        MenuLayout::Grid => (0.0, 0.0, 0.0, 0.0),
+    };
+    for (bx, by) in item_points(st, 0.0, 0.0) {
+        x0 = x0.min(bx - g.item_radius);
+        y0 = y0.min(by - g.item_radius);
+        x1 = x1.max(bx + g.item_radius);
+        y1 = y1.max(by + g.item_radius);
+    }
+    let r = g.center_radius;
+    (x0.min(-r), y0.min(-r), x1.max(r), y1.max(r))
+}
+
+// `waydo render`: draws a menu as it looks when just opened to a PNG file,
+// without a window or a compositor.
+fn render_menu(name: &str, out: &Path) -> Result<(), String> {
+    const MARGIN: f64 = 8.0;
+
+    let config = load_config()?;
+    if !config.menus.contains_key(name) {
+        return Err(format!("no menu named `{name}`"));
+    }
+    let mut st = State {
+        menus: config.menus,
+        active: name.to_string(),
+        settings: config.settings,
+        scale_factor: 1,
+        ..State::default()
+    };
+    // Filtered for whatever window has focus right now.
+    if conditional(st.menu()) {
+        st.window = actions::focused_window(st.compositor());
+    }
+    refresh_view(&mut st);
+    // Icons come from the display's icon theme; without one, labels are
+    // drawn instead.
+    if gtk::init().is_ok() {
+        reload_icons(&mut st);
+    } else {
+        let size = st.settings.geometry.item_radius as i32;
+        st.images = load_images(&st.menus, 2 * size, 1);
+    }
+
+    let (x0, y0, x1, y1) = menu_extent(&st);
+    let (w, h) = (
+        (x1 - x0 + 2.0 * MARGIN).ceil() as i32,
+        (y1 - y0 + 2.0 * MARGIN).ceil() as i32,
+    );
+    st.visible = true;
+    anchor_at(&mut st, MARGIN - x0, MARGIN - y0);
+    st.fade = None;
+    st.opacity = 1.0;
+
+    let surface = gtk::cairo::ImageSurface::create(gtk::cairo::Format::ARgb32, w, h)
+        .map_err(|e| format!("failed to create a {w}x{h} image: {e}"))?;
+    {
+        let cr = gtk::cairo::Context::new(&surface).map_err(|e| e.to_string())?;
+        draw_ui(&cr, w, h, &st);
+    }
+    let mut file = fs::File::create(out).map_err(|e| format!("{}: {}", out.display(), e))?;
+    surface
+        .write_to_png(&mut file)
+        .map_err(|e| format!("{}: {}", out.display(), e))
+}
+
+// Logical size of the overlay: the drawing area's allocation, or before the
+// first one, when it reports 0x0, the geometry of the output it covers. A
+// later allocation refits the menu through the resize handler.
+fn surface_size(win: &ApplicationWindow, da: &DrawingArea) -> Option<(f64, f64)> {
+    if da.width() > 0 && da.height() > 0 {
+        return Some((da.width() as f64, da.height() as f64));
+    }
+    let monitor = win.monitor().or_else(|| {
+        gdk::Display::default()?
+            .monitors()
+            .item(0)
+            .and_downcast::<gdk::Monitor>()
+    })?;
+    let geometry = monitor.geometry();
+    Some((geometry.width() as f64, geometry.height() as f64))
+}
+
+// Wayland doesn't tell clients where the pointer is outside their own
+// surfaces, so the compositor's focused output stands in for the one under
+// the cursor (they agree with focus-follows-mouse). None leaves the choice
+// to the compositor.
+fn monitor_named(name: Option<String>) -> Option<gdk::Monitor> {
+    let name = name?;
+    let monitors = gdk::Display::default()?.monitors();
+    (0..monitors.n_items())
+        .filter_map(|i| monitors.item(i).and_downcast::<gdk::Monitor>())
+        .find(|m| m.connector().is_some_and(|c| c == name))
 }
 
-fn show_menu(st: &mut State, win: &ApplicationWindow, da: &DrawingArea) {
+fn show_menu_on(st: &mut State, monitor: Option<gdk::Monitor>) {
     st.visible = true;
+    st.last_input = Some(Instant::now());
     st.anchored = false;
+    st.touch_anchored = false;
+    st.mapped_at = None;
+    st.early_pointer = None;
+    st.highlight = None;
+    st.gesture_armed = false;
+    st.path.clear();
+    st.chain.clear();
+    refresh_view(st);
+    // The fade-in starts once the menu is anchored and actually drawn.
+    st.fade = None;
+    st.nav = None;
+    st.opacity = if st.settings.fade_ms == 0 { 1.0 } else { 0.0 };
+    if monitor.is_some() {
+        st.output = monitor.as_ref().and_then(|m| m.connector());
+    }
+    st.pending.push(WindowOp::Show(monitor));
+}
+
+// Where a menu just shown anchors; None waits for the pointer to move, as
+// does anything that can't be resolved yet.
+fn spawn_anchor(st: &State, win: &ApplicationWindow, da: &DrawingArea) -> Option<(f64, f64)> {
+    let size = surface_size(win, da);
+    // A position kept from a larger output may be off this one.
+    let last_root = st.last_root.filter(|&(x, y)| {
+        size.is_some_and(|(w, h)| (0.0..w).contains(&x) && (0.0..h).contains(&y))
+    });
+    match st.settings.spawn {
+        _ if st.settings.reuse_position && last_root.is_some() => last_root,
+        // Asked right after mapping, the surface only knows where the
+        // pointer was last seen over it; where it is now comes with the
+        // pointer entering it, see `pointer_entered`.
+        Spawn::Motion | Spawn::Pointer => None,
+        Spawn::Center => size.map(|(w, h)| (w / 2.0, h / 2.0)),
+    }
+}
+
+// Runs `f` on the state, then carries out the window changes it queued once
+// the borrow is released. Mapping and unmapping the window emit signals
+// (focus, scale, size) whose handlers borrow the state themselves, so every
+// handler goes through here instead of holding the borrow across them.
+fn update<R>(
+    state: &Rc<RefCell<State>>,
+    win: &ApplicationWindow,
+    da: &DrawingArea,
+    f: impl FnOnce(&mut State) -> R,
+) -> R {
+    let result = f(&mut state.borrow_mut());
+    let ops = std::mem::take(&mut state.borrow_mut().pending);
+    for op in ops {
+        log::debug!("window {:?}", op);
+        match op {
+            WindowOp::Show(monitor) => {
+                // Bound before mapping: a layer surface stays on its output
+                // once shown. Setting another one remaps it there.
+                win.set_monitor(monitor.as_ref());
+                win.present();
+                let mut st = state.borrow_mut();
+                if let Some(size) = surface_size(win, da) {
+                    st.surface = size;
+                }
+                if st.visible && !st.anchored {
+                    match spawn_anchor(&st, win, da) {
+                        Some((x, y)) => anchor_at(&mut st, x, y),
+                        None => wait_for_anchor(&mut st, state, win, da),
+                    }
+                }
+                da.queue_draw();
+            }
+            WindowOp::Hide => win.hide(),
+            WindowOp::Keyboard(mode) => win.set_keyboard_mode(mode),
+        }
+    }
+    arm_idle_timer(state, win, da);
+    result
+}
+
+// Motion arriving right after the surface maps may be left over from before
+// it, or just GTK reporting where the pointer already is. Either way it's
+// only noted; when the grace period ends the menu anchors at the latest
+// position, so a stationary pointer still gets its menu. Motion after that,
+// or a click, anchors right away.
+fn wait_for_anchor(
+    st: &mut State,
+    state: &Rc<RefCell<State>>,
+    win: &ApplicationWindow,
+    da: &DrawingArea,
+) {
+    let mapped_at = Instant::now();
+    st.mapped_at = Some(mapped_at);
+    st.early_pointer = None;
+    let (state, win, da) = (state.clone(), win.clone(), da.clone());
+    glib::timeout_add_local_once(ANCHOR_GRACE, move || {
+        update(&state, &win, &da, |st| {
+            // A later showing has its own timer.
+            if st.mapped_at != Some(mapped_at) || !st.visible || st.anchored {
+                return;
+            }
+            if let Some((x, y)) = st.early_pointer.take() {
+                anchor_at(st, x, y);
+                da.queue_draw();
+            }
+        });
+    });
+}
+
+// While the menu is shown with `idle_hide_ms` set, keeps one timer pending
+// for when the menu would have been idle that long. Input since it was set
+// just pushes the next check back.
+fn arm_idle_timer(state: &Rc<RefCell<State>>, win: &ApplicationWindow, da: &DrawingArea) {
+    let mut st = state.borrow_mut();
+    let limit = Duration::from_millis(st.settings.idle_hide_ms);
+    if !st.visible || limit.is_zero() || st.idle_timer {
+        return;
+    }
+    st.idle_timer = true;
+    let idle = st.last_input.map_or(Duration::ZERO, |t| t.elapsed());
+    let (state, win, da) = (state.clone(), win.clone(), da.clone());
+    glib::timeout_add_local_once(limit.saturating_sub(idle), move || {
+        update(&state, &win, &da, |st| {
+            st.idle_timer = false;
+            let limit = Duration::from_millis(st.settings.idle_hide_ms);
+            if st.visible && !limit.is_zero() && st.last_input.is_some_and(|t| t.elapsed() >= limit)
+            {
+                log::debug!("idle for {:?}, closing", limit);
+                hide_menu(st);
+            }
+        });
+    });
+}
+
+// Shows the named menu. Re-opening the visible menu closes it; opening a
+// different one while visible swaps the root in place at the pointer.
+// Either way the compositor is asked first which output has focus, and
+// `poll_opening` carries on once it answers.
+fn open_menu(st: &mut State, name: &str) {
+    if !st.menus.contains_key(name) {
+        eprintln!("waydo: no menu named `{}`", name);
+        return;
+    }
+
+    if st.visible && st.active == name {
+        hide_menu(st);
+        return;
+    }
+
+    // Already asked; the answer opens this menu instead.
+    if let Some(opening) = &mut st.opening {
+        opening.menu = name.to_string();
+        return;
+    }
+    st.opening = Some(Opening {
+        menu: name.to_string(),
+        since: Instant::now(),
+        focus: actions::query_focus(st.compositor(), st.menus.values().any(conditional)),
+    });
+}
+
+// Opens the menu waiting in `st.opening` once the compositor has answered,
+// or without its answer once `FOCUS_WAIT` is up.
+fn poll_opening(st: &mut State, da: &DrawingArea) {
+    let Some(opening) = st.opening.take() else {
+        return;
+    };
+    let focus = match opening.focus.try_recv() {
+        Ok(focus) => focus,
+        Err(mpsc::TryRecvError::Empty) if opening.since.elapsed() < FOCUS_WAIT => {
+            st.opening = Some(opening);
+            return;
+        }
+        Err(mpsc::TryRecvError::Empty) => {
+            eprintln!("waydo: the compositor didn't say what has focus in time");
+            actions::Focus::default()
+        }
+        Err(mpsc::TryRecvError::Disconnected) => actions::Focus::default(),
+    };
+    let name = opening.menu;
+    // A reload in the meantime may have dropped it.
+    if !st.menus.contains_key(&name) {
+        return;
+    }
+    st.window = focus.window;
+    let target = monitor_named(focus.output);
+
+    if !st.visible {
+        st.active = name;
+        show_menu_on(st, target);
+        return;
+    }
+
+    // Opened from another output: show it there rather than switching in
+    // place.
+    if let Some(monitor) = &target
+        && monitor.connector() != st.output
+    {
+        st.active = name;
+        show_menu_on(st, target);
+        return;
+    }
+
+    st.active = name;
     st.path.clear();
-    win.present();
+    st.chain.clear();
+    st.highlight = None;
+    refresh_view(st);
+    if st.anchored {
+        let (x, y) = (st.px, st.py);
+        anchor_at(st, x, y);
+    }
     da.queue_draw();
 }
 
-fn send_toggle() -> std::io::Result<()> {
-    let mut stream = UnixStream::connect("/tmp/waydo.sock")?;
-    stream.write_all(b"TOGGLE\n")?;
+fn reload_config(st: &mut State, da: &DrawingArea) {
+    match load_config() {
+        Ok(config) => {
+            st.menus = config.menus;
+            if !st.menus.contains_key(&st.active) {
+                st.active = config::ROOT_MENU.to_string();
+                st.path.clear();
+            }
+            let menus = &st.menus;
+            st.chain.retain(|name| menus.contains_key(name));
+            st.settings = config.settings;
+            reload_icons(st);
+            if st.visible {
+                refresh_view(st);
+                da.queue_draw();
+            }
+        }
+        Err(e) => eprintln!("waydo: reload failed, keeping previous config: {}", e),
+    }
+}
+
+// Reloads the config whenever the theme's stylesheet is saved, for as long as
+// the config names that file.
+fn watch_stylesheet(st: &mut State, tx: &Sender<Command>) {
+    let path = st.settings.stylesheet.clone();
+    if st.stylesheet_monitor.as_ref().map(|(p, _)| p) == path.as_ref() {
+        return;
+    }
+    if let Some((_, monitor)) = st.stylesheet_monitor.take() {
+        monitor.cancel();
+    }
+    let Some(path) = path else {
+        return;
+    };
+    let file = gio::File::for_path(&path);
+    match file.monitor_file(gio::FileMonitorFlags::WATCH_MOVES, gio::Cancellable::NONE) {
+        Ok(monitor) => {
+            let tx = tx.clone();
+            monitor.connect_changed(move |_, _, _, event| {
+                // Editors that save by renaming a new file over the old one
+                // show up as a move.
+                if matches!(
+                    event,
+                    gio::FileMonitorEvent::ChangesDoneHint
+                        | gio::FileMonitorEvent::Created
+                        | gio::FileMonitorEvent::MovedIn
+                        | gio::FileMonitorEvent::Renamed
+                ) {
+                    log::debug!("stylesheet changed, reloading");
+                    let _ = tx.send(Command::Reload);
+                }
+            });
+            st.stylesheet_monitor = Some((path, monitor));
+        }
+        Err(e) => eprintln!("waydo: can't watch {}: {}", path.display(), e),
+    }
+}
+
+// `--socket` wins over $WAYDO_SOCKET, which wins over the per-user runtime dir.
+fn socket_path(flag: Option<PathBuf>) -> PathBuf {
+    if let Some(path) = flag {
+        return path;
+    }
+    match env::var_os("WAYDO_SOCKET") {
+        Some(path) if !path.is_empty() => return PathBuf::from(path),
+        _ => {}
+    }
+    match env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir).join("waydo.sock"),
+        _ => PathBuf::from(format!("/tmp/waydo-{}.sock", unsafe { libc::getuid() })),
+    }
+}
+
+fn send_command(socket: &Path, cmd: &str) -> std::io::Result<()> {
+    let mut stream = UnixStream::connect(socket)?;
+    stream.write_all(format!("{cmd}\n").as_bytes())?;
     Ok(())
 }
 
-fn run_daemon() {
-    let app = Application::builder()
-        .application_id("io.github.waydo")
-        .build();
+// With `--autostart`, a command that finds no daemon starts one and is sent
+// once more. The first error is the one reported if that fails too.
+fn send_or_start(socket: &Path, cmd: &str, autostart: bool) -> std::io::Result<()> {
+    match send_command(socket, cmd) {
+        Err(e)
+            if autostart
+                && matches!(
+                    e.kind(),
+                    std::io::ErrorKind::ConnectionRefused | std::io::ErrorKind::NotFound
+                ) =>
+        {
+            if start_daemon(socket) {
+                send_command(socket, cmd).map_err(|_| e)
+            } else {
+                Err(e)
+            }
+        }
+        result => result,
+    }
+}
+
+// Runs `waydo daemon` on the same socket in its own process group, so it
+// outlives this client and the terminal it was started from.
+fn start_daemon(socket: &Path) -> bool {
+    use std::os::unix::process::CommandExt;
+
+    let spawned = env::current_exe().and_then(|exe| {
+        std::process::Command::new(exe)
+            .arg("--socket")
+            .arg(socket)
+            .arg("daemon")
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .process_group(0)
+            .spawn()
+    });
+    if let Err(e) = spawned {
+        eprintln!("waydo: failed to start the daemon: {}", e);
+        return false;
+    }
+    let deadline = Instant::now() + AUTOSTART_WAIT;
+    while Instant::now() < deadline {
+        if UnixStream::connect(socket).is_ok() {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    false
+}
+
+// Sends `cmd` and waits for the daemon's one-line answer.
+fn query(socket: &Path, cmd: &str) -> std::io::Result<String> {
+    let mut stream = UnixStream::connect(socket)?;
+    stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+    stream.write_all(format!("{cmd}\n").as_bytes())?;
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    if reply.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "no answer from daemon",
+        ));
+    }
+    Ok(reply.trim_end().to_string())
+}
+
+// Refuses to start over a live daemon. The socket file is only removed when
+// nothing answers on it, i.e. it was left behind by a dead process.
+fn claim_socket(socket: &Path) {
+    match UnixStream::connect(socket) {
+        Ok(mut stream) => {
+            let _ = stream.set_read_timeout(Some(Duration::from_millis(500)));
+            let _ = stream.write_all(b"PING\n");
+            let mut reply = String::new();
+            let _ = BufReader::new(stream).read_line(&mut reply);
+            if reply.trim() == "PONG" {
+                eprintln!("waydo: daemon already running on {}", socket.display());
+            } else {
+                eprintln!("waydo: {} is in use by another process", socket.display());
+            }
+            std::process::exit(1);
+        }
+        Err(_) => {
+            if socket.exists() {
+                let _ = std::fs::remove_file(socket);
+            }
+        }
+    }
+}
+
+fn run_daemon(socket: PathBuf) {
+    claim_socket(&socket);
+
+    let config = match load_config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("waydo: invalid menu config: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let compositor = config
+        .settings
+        .compositor
+        .unwrap_or_else(actions::detect_compositor);
+    let warnings = actions::missing_tools(compositor);
+    for warning in &warnings {
+        eprintln!("waydo: warning: {}", warning);
+    }
+
+    let app = Application::builder().application_id(DBUS_NAME).build();
 
-    app.connect_activate(|app| {
+    let socket_file = socket.clone();
+    app.connect_activate(move |app| {
         install_transparent_css();
 
-        let state = Rc::new(RefCell::new(State::default()));
+        // A service manager stops the daemon with SIGTERM; leave through
+        // the main loop so `shutdown` still runs.
+        for signal in [libc::SIGINT, libc::SIGTERM] {
+            let app = app.clone();
+            glib::unix_signal_add_local(signal, move || {
+                log::debug!("signal {}, quitting", signal);
+                app.quit();
+                glib::ControlFlow::Break
+            });
+        }
+
+        let state = Rc::new(RefCell::new(State {
+            menus: config.menus.clone(),
+            active: config::ROOT_MENU.to_string(),
+            settings: config.settings.clone(),
+            warnings: warnings.clone(),
+            scale_factor: 1,
+            usage: usage::Usage::load(),
+            ..State::default()
+        }));
+        reload_icons(&mut state.borrow_mut());
 
         let win = ApplicationWindow::builder()
             .application(app)
@@ -881,7 +2816,7 @@ fn run_daemon() {
         win.init_layer_shell();
         win.set_namespace(Some("waydo"));
         win.set_layer(Layer::Overlay);
-        win.set_keyboard_mode(KeyboardMode::None);
+        win.set_keyboard_mode(KeyboardMode::OnDemand);
 
         win.set_anchor(Edge::Top, true);
         win.set_anchor(Edge::Bottom, true);
@@ -899,173 +2834,1184 @@ fn run_daemon() {
             });
         }
 
-        win.set_child(Some(&da));
-        win.hide();
-
-        let motion = gtk::EventControllerMotion::new();
+        // Pointer coordinates and Cairo drawing are both in logical pixels,
+        // so hit-testing needs no scaling; only icon rasters do.
+        {
+            let state = state.clone();
+            da.connect_scale_factor_notify(move |da| {
+                let mut st = state.borrow_mut();
+                st.scale_factor = da.scale_factor();
+                reload_icons(&mut st);
+                da.queue_draw();
+            });
+        }
+
+        {
+            let state = state.clone();
+            da.connect_resize(move |da, w, h| {
+                let mut st = state.borrow_mut();
+                st.surface = (w as f64, h as f64);
+                // A menu anchored before the first allocation couldn't be
+                // kept on screen yet.
+                if st.anchored {
+                    (st.cx, st.cy) = on_screen(&st, st.cx, st.cy);
+                    da.queue_draw();
+                }
+            });
+        }
+
+        win.set_child(Some(&da));
+        win.hide();
+
+        // Sees every event before the other controllers do, for
+        // `idle_hide_ms`.
+        let input = gtk::EventControllerLegacy::new();
+        input.set_propagation_phase(gtk::PropagationPhase::Capture);
+        {
+            let state = state.clone();
+            input.connect_event(move |_, _| {
+                state.borrow_mut().last_input = Some(Instant::now());
+                glib::Propagation::Proceed
+            });
+        }
+        win.add_controller(input);
+
+        // The wheel turns a ring to bring items clipped by the screen edge
+        // into reach.
+        let scroll = gtk::EventControllerScroll::new(
+            gtk::EventControllerScrollFlags::VERTICAL | gtk::EventControllerScrollFlags::DISCRETE,
+        );
+        {
+            let state = state.clone();
+            let win2 = win.clone();
+            let da2 = da.clone();
+            scroll.connect_scroll(move |_, _, dy| {
+                update(&state, &win2, &da2, |st| {
+                    if !st.visible
+                        || !st.anchored
+                        || st.layout() == MenuLayout::Grid
+                        || st.settings.wheel_step == 0.0
+                    {
+                        return glib::Propagation::Proceed;
+                    }
+                    st.rotation += dy * st.settings.wheel_step.to_radians();
+                    st.highlight = item_at(st, st.px, st.py);
+                    da2.queue_draw();
+                    glib::Propagation::Stop
+                })
+            });
+        }
+        da.add_controller(scroll);
+
+        let motion = gtk::EventControllerMotion::new();
+        {
+            let state = state.clone();
+            let win2 = win.clone();
+            let da2 = da.clone();
+            motion.connect_motion(move |_, x, y| {
+                update(&state, &win2, &da2, |st| match pointer_moved(st, x, y) {
+                    Moved::Nothing => {}
+                    Moved::Redraw => da2.queue_draw(),
+                    Moved::Crossed(idx) => {
+                        activate_item(st, idx, false, gdk::ModifierType::empty(), (x, y), &da2)
+                    }
+                });
+            });
+        }
+        {
+            let state = state.clone();
+            let win2 = win.clone();
+            let da2 = da.clone();
+            motion.connect_enter(move |_, x, y| {
+                update(&state, &win2, &da2, |st| {
+                    if pointer_entered(st, x, y) {
+                        da2.queue_draw();
+                    }
+                });
+            });
+        }
+        da.add_controller(motion);
+
+        let keys = gtk::EventControllerKey::new();
+        {
+            let state = state.clone();
+            let win2 = win.clone();
+            let da2 = da.clone();
+            keys.connect_key_pressed(move |_, key, _, mods| {
+                update(&state, &win2, &da2, |st| handle_key(st, key, mods, &da2))
+            });
+        }
+        win.add_controller(keys);
+
+        let click = gtk::GestureClick::new();
+        click.set_button(0);
+
+        {
+            let state = state.clone();
+            let win2 = win.clone();
+            let da2 = da.clone();
+            click.connect_pressed(move |gesture, _n_press, x, y| {
+                if is_touch(gesture) {
+                    return;
+                }
+                let button = gesture.current_button();
+                update(&state, &win2, &da2, |st| {
+                    if pressed_at(st, button, x, y) {
+                        da2.queue_draw();
+                    }
+                });
+            });
+        }
+
         {
             let state = state.clone();
+            let win2 = win.clone();
             let da2 = da.clone();
-            motion.connect_motion(move |_, x, y| {
-                let mut st = state.borrow_mut();
 
-                if st.visible && !st.anchored {
-                    st.anchored = true;
-                    st.px = x;
-                    st.py = y;
-                    st.cx = x;
-                    st.cy = y;
-                    st.root_cx = x;
-                    st.root_cy = y;
-                    da2.queue_draw();
+            click.connect_released(move |gesture, _n_press, x, y| {
+                // Touches are handled by their own gestures below.
+                if is_touch(gesture) {
+                    return;
                 }
+                let button = gesture.current_button();
+                let mods = gesture.current_event_state();
+                update(&state, &win2, &da2, |st| {
+                    match released_at(st, button, x, y) {
+                        Release::Nothing => {}
+                        Release::Back => go_back(st, x, y, &da2),
+                        Release::Hide => hide_menu(st),
+                        Release::Anchor => {
+                            anchor_at(st, x, y);
+                            da2.queue_draw();
+                        }
+                        Release::Activate(idx) => activate_item(st, idx, false, mods, (x, y), &da2),
+                        Release::Select => select_at(st, x, y, mods, &da2),
+                    }
+                });
             });
         }
-        da.add_controller(motion);
 
-        let click = gtk::GestureClick::new();
-        click.set_button(0);
+        da.add_controller(click);
 
+        // Touch doesn't move the pointer, so there is no motion to anchor
+        // on: the finger going down anchors the menu instead, and lifting
+        // it selects whatever it is over, marking-menu style.
+        let touch = gtk::GestureDrag::new();
+        touch.set_touch_only(true);
         {
             let state = state.clone();
             let win2 = win.clone();
             let da2 = da.clone();
-
-            click.connect_released(move |_, _n_press, x, y| {
-                let mut st = state.borrow_mut();
-                if !st.visible {
-                    return;
-                }
-
-                if !st.anchored {
-                    st.anchored = true;
-                    st.cx = x;
-                    st.cy = y;
-                    st.root_cx = x;
-                    st.root_cy = y;
-                    da2.queue_draw();
+            touch.connect_drag_begin(move |_, x, y| {
+                update(&state, &win2, &da2, |st| {
+                    if st.visible && !st.anchored {
+                        anchor_at(st, x, y);
+                        st.touch_anchored = true;
+                        da2.queue_draw();
+                    }
+                });
+            });
+        }
+        {
+            let state = state.clone();
+            let win2 = win.clone();
+            let da2 = da.clone();
+            touch.connect_drag_update(move |gesture, dx, dy| {
+                let Some((x0, y0)) = gesture.start_point() else {
                     return;
-                }
-
-                let center_r = CENTER_RADIUS;
-                if dist2(x, y, st.cx, st.cy) <= center_r * center_r {
-                    if st.path.is_empty() {
-                        hide_menu(&mut st, &win2, &da2);
-                    } else {
-                        st.path.pop();
-                        st.cx = x;
-                        st.cy = y;
+                };
+                update(&state, &win2, &da2, |st| {
+                    if !st.visible {
+                        return;
+                    }
+                    let hit = item_at(st, x0 + dx, y0 + dy);
+                    if hit != st.highlight {
+                        st.highlight = hit;
+                        hover_feedback(st);
                         da2.queue_draw();
                     }
+                });
+            });
+        }
+        {
+            let state = state.clone();
+            let win2 = win.clone();
+            let da2 = da.clone();
+            touch.connect_drag_end(move |gesture, dx, dy| {
+                let Some((x0, y0)) = gesture.start_point() else {
                     return;
-                }
+                };
+                let (x, y) = (x0 + dx, y0 + dy);
+                update(&state, &win2, &da2, |st| {
+                    if !st.visible {
+                        return;
+                    }
+                    // Lifting the finger that anchored the menu without
+                    // leaving the center keeps it open, to be tapped next.
+                    let center_r = st.settings.geometry.center_radius;
+                    if std::mem::take(&mut st.touch_anchored)
+                        && dist2(x, y, st.cx, st.cy) <= center_r * center_r
+                    {
+                        return;
+                    }
+                    log::debug!("touch lifted at ({:.1}, {:.1})", x, y);
+                    select_at(st, x, y, gdk::ModifierType::empty(), &da2);
+                });
+            });
+        }
+        da.add_controller(touch);
 
-                let items = current_items(&st.path);
-                let n = items.len();
-                if n == 0 {
-                    return;
-                }
+        // Holding a finger on the center closes the menu from any depth.
+        let long_press = gtk::GestureLongPress::new();
+        long_press.set_touch_only(true);
+        {
+            let state = state.clone();
+            let win2 = win.clone();
+            let da2 = da.clone();
+            long_press.connect_pressed(move |_, x, y| {
+                update(&state, &win2, &da2, |st| {
+                    let center_r = st.settings.geometry.center_radius;
+                    if st.visible
+                        && st.anchored
+                        && !st.touch_anchored
+                        && dist2(x, y, st.cx, st.cy) <= center_r * center_r
+                    {
+                        hide_menu(st);
+                    }
+                });
+            });
+        }
+        da.add_controller(long_press);
 
-                let dist = ITEM_RING_DISTANCE;
-                let deadzone = CENTER_RADIUS;
-                let points = ring_layout(n, st.cx, st.cy, dist);
-                let idx = match closest_index_for_pointer(x, y, st.cx, st.cy, &points, deadzone) {
-                    Some(i) if i < n => i,
-                    _ => return,
-                };
+        {
+            let state = state.clone();
+            let win2 = win.clone();
+            let da2 = da.clone();
+            glib::timeout_add_local(Duration::from_millis(TICK_MS), move || {
+                update(&state, &win2, &da2, |st| tick_animations(st, &da2));
+                glib::ControlFlow::Continue
+            });
+        }
 
-                let radius = ITEM_RADIUS;
-                let inner_ring = dist - radius;
-                let quick_click = dist2(x, y, st.cx, st.cy) <= inner_ring * inner_ring;
+        let (tx, rx) = std::sync::mpsc::channel::<Command>();
+        watch_stylesheet(&mut state.borrow_mut(), &tx);
 
-                match items[idx].kind {
-                    ItemKind::Action(action) => {
-                        run_action(action, &mut st, &win2, &da2);
-                    }
-                    ItemKind::Submenu { on_click, .. } => {
-                        if let Some(mut action) = on_click {
-                            if quick_click {
-                                action.close_on_click = true;
-                                run_action(action, &mut st, &win2, &da2);
-                                return;
+        {
+            let state = state.clone();
+            let win2 = win.clone();
+            let da2 = da.clone();
+            let reload_tx = tx.clone();
+            glib::timeout_add_local(Duration::from_millis(16), move || {
+                let cmds: Vec<Command> = rx.try_iter().collect();
+                if cmds.is_empty() {
+                    return glib::ControlFlow::Continue;
+                }
+                update(&state, &win2, &da2, |st| {
+                    let debounce = Duration::from_millis(st.settings.toggle_debounce_ms);
+                    let cmds =
+                        coalesce_toggles(cmds, &mut st.last_toggle, Instant::now(), debounce);
+                    for cmd in cmds {
+                        log::debug!("command {:?}", cmd);
+                        match cmd {
+                            Command::Toggle => {
+                                if st.visible || st.opening.is_some() {
+                                    hide_menu(st);
+                                } else {
+                                    open_menu(st, config::ROOT_MENU);
+                                }
+                            }
+                            Command::Reload => {
+                                reload_config(st, &da2);
+                                watch_stylesheet(st, &reload_tx);
                             }
-                            run_action(action, &mut st, &win2, &da2);
+                            Command::Show => {
+                                if !st.visible && st.opening.is_none() {
+                                    open_menu(st, config::ROOT_MENU);
+                                }
+                            }
+                            Command::Hide => {
+                                if st.visible || st.opening.is_some() {
+                                    hide_menu(st);
+                                }
+                            }
+                            Command::Status(reply) => {
+                                let _ = reply.send(st.status());
+                            }
+                            Command::Open(name) => open_menu(st, &name),
                         }
-                        st.path.push(idx);
-                        st.cx = x;
-                        st.cy = y;
-                        da2.queue_draw();
                     }
-                }
+                });
+                glib::ControlFlow::Continue
             });
         }
 
-        da.add_controller(click);
+        {
+            let tx = tx.clone();
+            glib::unix_signal_add_local(libc::SIGHUP, move || {
+                let _ = tx.send(Command::Reload);
+                glib::ControlFlow::Continue
+            });
+        }
 
-        let (tx, rx) = std::sync::mpsc::channel::<String>();
+        {
+            let state = state.clone();
+            let da2 = da.clone();
+            watch_color_scheme(move |scheme| {
+                log::debug!("system color scheme is {:?}", scheme);
+                let mut st = state.borrow_mut();
+                st.system_scheme = scheme;
+                if st.visible {
+                    da2.queue_draw();
+                }
+            });
+        }
 
         {
             let state = state.clone();
-            let win2 = win.clone();
             let da2 = da.clone();
-            glib::timeout_add_local(std::time::Duration::from_millis(16), move || {
-                while let Ok(msg) = rx.try_recv() {
-                    if msg == "TOGGLE" {
-                        let mut st = state.borrow_mut();
-                        if st.visible {
-                            hide_menu(&mut st, &win2, &da2);
-                        } else {
-                            show_menu(&mut st, &win2, &da2);
-                        }
+            win.connect_is_active_notify(move |win| {
+                let active = win.is_active();
+                update(&state, win, &da2, |st| {
+                    // With `click_through`, a click outside the menu lands
+                    // on another window and takes the keyboard focus with it.
+                    if !active
+                        && st.visible
+                        && st.anchored
+                        && st.settings.click_through
+                        && !st.keyboard_released
+                    {
+                        log::debug!("lost focus, closing");
+                        hide_menu(st);
                     }
-                }
-                glib::ControlFlow::Continue
+                });
             });
         }
 
-        thread::spawn(move || {
-            let socket_path = "/tmp/waydo.sock";
-            if Path::new(socket_path).exists() {
-                let _ = std::fs::remove_file(socket_path);
-            }
+        export_dbus_object(app, tx.clone());
 
-            let listener = match UnixListener::bind(socket_path) {
+        let socket_path = socket.clone();
+        thread::spawn(move || {
+            let listener = match UnixListener::bind(&socket_path) {
                 Ok(l) => l,
                 Err(e) => {
-                    eprintln!("waydo: failed to bind {}: {}", socket_path, e);
+                    eprintln!("waydo: failed to bind {}: {}", socket_path.display(), e);
                     return;
                 }
             };
 
-            for stream in listener.incoming() {
-                if let Ok(stream) = stream {
-                    let mut reader = BufReader::new(stream);
-                    let mut line = String::new();
-                    if reader.read_line(&mut line).is_ok() && line.trim() == "TOGGLE" {
-                        let _ = tx.send("TOGGLE".to_string());
-                    }
-                }
+            for stream in listener.incoming().flatten() {
+                let tx = tx.clone();
+                thread::spawn(move || handle_client(stream, &tx));
             }
         });
     });
 
     app.run_with_args(&["waydo"]);
+    shutdown(&socket_file);
+}
+
+// Leaves nothing behind: the socket file, and no keys held down.
+fn shutdown(socket: &Path) {
+    actions::release_all();
+    match fs::remove_file(socket) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            eprintln!("waydo: failed to remove {}: {}", socket.display(), e);
+        }
+        _ => {}
+    }
+}
+
+// Both the application id, which GApplication owns on the session bus, and
+// the name of the interface below.
+const DBUS_NAME: &str = "io.github.waydo";
+const DBUS_INTERFACE: &str = r#"
+<node>
+  <interface name="io.github.waydo">
+    <method name="Toggle"/>
+    <method name="Show"/>
+    <method name="Hide"/>
+    <method name="Open">
+      <arg name="name" type="s" direction="in"/>
+    </method>
+  </interface>
+</node>
+"#;
+
+// Session-bus counterpart of the socket: method calls become the same
+// commands and go through the same pump. GApplication already owns the bus
+// name, so the interface is only added to the application's own object,
+// next to org.gtk.Application. Without a session bus only the D-Bus route
+// is lost.
+fn export_dbus_object(app: &Application, tx: Sender<Command>) {
+    let (Some(conn), Some(path)) = (app.dbus_connection(), app.dbus_object_path()) else {
+        eprintln!("waydo: not on the session bus, D-Bus methods are unavailable");
+        return;
+    };
+    let interface = match gio::DBusNodeInfo::for_xml(DBUS_INTERFACE) {
+        Ok(node) => node.lookup_interface(DBUS_NAME),
+        Err(e) => {
+            eprintln!("waydo: bad D-Bus interface: {}", e);
+            return;
+        }
+    };
+    let Some(interface) = interface else {
+        return;
+    };
+    let registered = conn
+        .register_object(&path, &interface)
+        .method_call(move |_, _, _, _, method, params, invocation| {
+            let cmd = match method {
+                "Toggle" => Command::Toggle,
+                "Show" => Command::Show,
+                "Hide" => Command::Hide,
+                "Open" => match params.get::<(String,)>() {
+                    Some((name,)) => Command::Open(name),
+                    None => {
+                        invocation.return_error(
+                            gio::IOErrorEnum::InvalidArgument,
+                            "Open takes a menu name",
+                        );
+                        return;
+                    }
+                },
+                _ => {
+                    invocation.return_error(gio::IOErrorEnum::NotSupported, "unknown method");
+                    return;
+                }
+            };
+            let _ = tx.send(cmd);
+            invocation.return_value(None);
+        })
+        .build();
+    if let Err(e) = registered {
+        eprintln!("waydo: failed to export D-Bus object: {}", e);
+    }
+}
+
+const PORTAL_NAME: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const PORTAL_SETTINGS: &str = "org.freedesktop.portal.Settings";
+const APPEARANCE: &str = "org.freedesktop.appearance";
+
+// Reports the system's light/dark preference right away and again whenever it
+// changes: from the settings portal, or GNOME's gsettings key without one.
+fn watch_color_scheme(on_change: impl Fn(ColorScheme) + 'static) {
+    let conn = match gio::bus_get_sync(gio::BusType::Session, gio::Cancellable::NONE) {
+        Ok(conn) => conn,
+        Err(e) => {
+            eprintln!(
+                "waydo: no session bus, not following the color scheme: {}",
+                e
+            );
+            return;
+        }
+    };
+    let current = conn.call_sync(
+        Some(PORTAL_NAME),
+        PORTAL_PATH,
+        PORTAL_SETTINGS,
+        "Read",
+        Some(&(APPEARANCE, "color-scheme").to_variant()),
+        None,
+        gio::DBusCallFlags::NONE,
+        1000,
+        gio::Cancellable::NONE,
+    );
+    match current {
+        Ok(reply) => {
+            on_change(portal_scheme(&reply.child_value(0)));
+            let subscription = conn.subscribe_to_signal(
+                Some(PORTAL_NAME),
+                Some(PORTAL_SETTINGS),
+                Some("SettingChanged"),
+                Some(PORTAL_PATH),
+                Some(APPEARANCE),
+                gio::DBusSignalFlags::NONE,
+                move |signal| {
+                    if let Some((_, key, value)) =
+                        signal.parameters.get::<(String, String, glib::Variant)>()
+                        && key == "color-scheme"
+                    {
+                        on_change(portal_scheme(&value));
+                    }
+                },
+            );
+            // Keep the subscription for as long as the daemon runs.
+            let _ = subscription.downgrade();
+        }
+        Err(e) => {
+            log::debug!("settings portal unavailable: {}", e);
+            let schema = gio::SettingsSchemaSource::default()
+                .and_then(|source| source.lookup("org.gnome.desktop.interface", true));
+            if !schema.is_some_and(|s| s.has_key("color-scheme")) {
+                return;
+            }
+            let settings = gio::Settings::new("org.gnome.desktop.interface");
+            let scheme = |s: &gio::Settings| match s.string("color-scheme").as_str() {
+                "prefer-light" => ColorScheme::Light,
+                _ => ColorScheme::Dark,
+            };
+            on_change(scheme(&settings));
+            settings.connect_changed(Some("color-scheme"), move |s, _| on_change(scheme(s)));
+            // Same as the subscription above: watched until exit.
+            std::mem::forget(settings);
+        }
+    }
+}
+
+// The portal's color-scheme value: 1 prefers dark, 2 light, 0 has no
+// preference, which keeps the dark palette. `Read` wraps it in an extra
+// variant layer, so unwrap as many as there are.
+fn portal_scheme(v: &glib::Variant) -> ColorScheme {
+    let mut v = v.clone();
+    while let Some(inner) = v.as_variant() {
+        v = inner;
+    }
+    match v.get::<u32>() {
+        Some(2) => ColorScheme::Light,
+        _ => ColorScheme::Dark,
+    }
+}
+
+// One tick's worth of client commands, arrived by `now`, as the pump acts on
+// them. Toggles bouncing in within the debounce window are dropped; whatever
+// remains of a burst nets out to its parity, so mashing the keybind always
+// ends in a predictable state: a single toggle, or none.
+fn coalesce_toggles(
+    cmds: Vec<Command>,
+    last_toggle: &mut Option<Instant>,
+    now: Instant,
+    debounce: Duration,
+) -> Vec<Command> {
+    let mut coalesced = Vec::with_capacity(cmds.len());
+    let mut toggles = 0;
+    for cmd in cmds {
+        if let Command::Toggle = cmd {
+            if accept_toggle(last_toggle, now, debounce) {
+                toggles += 1;
+            }
+            continue;
+        }
+        if toggles % 2 == 1 {
+            coalesced.push(Command::Toggle);
+        }
+        toggles = 0;
+        coalesced.push(cmd);
+    }
+    if toggles % 2 == 1 {
+        coalesced.push(Command::Toggle);
+    }
+    coalesced
+}
+
+// Whether a toggle arriving at `now` should act, given when the last one
+// that did. Records it if so.
+fn accept_toggle(last: &mut Option<Instant>, now: Instant, debounce: Duration) -> bool {
+    if debounce > Duration::ZERO
+        && last.is_some_and(|t| now.saturating_duration_since(t) < debounce)
+    {
+        return false;
+    }
+    *last = Some(now);
+    true
+}
+
+// A client command for the GTK thread, from the socket or D-Bus, with where
+// to send the answer for commands that have one.
+#[derive(Debug)]
+enum Command {
+    Toggle,
+    // Show and Hide do nothing if the menu already is
+    Show,
+    Hide,
+    Reload,
+    Open(String),
+    // Answered with the `waydo status` line
+    Status(Sender<String>),
+}
+
+// Serves one client connection: every line is a command. A client that
+// stalls is dropped after a second, but whatever it had sent is still used.
+fn handle_client(stream: UnixStream, tx: &Sender<Command>) {
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(1)));
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let result = reader.read_line(&mut line);
+        match line.trim() {
+            "PING" => {
+                let _ = reader.get_mut().write_all(b"PONG\n");
+            }
+            "TOGGLE" => {
+                let _ = tx.send(Command::Toggle);
+            }
+            "RELOAD" => {
+                let _ = tx.send(Command::Reload);
+            }
+            "SHOW" => {
+                let _ = tx.send(Command::Show);
+            }
+            "HIDE" => {
+                let _ = tx.send(Command::Hide);
+            }
+            msg if msg.starts_with("OPEN ") => {
+                let _ = tx.send(Command::Open(msg["OPEN ".len()..].to_string()));
+            }
+            "STATUS" => {
+                let (reply_tx, reply_rx) = mpsc::channel();
+                let _ = tx.send(Command::Status(reply_tx));
+                if let Ok(status) = reply_rx.recv_timeout(Duration::from_secs(1)) {
+                    let _ = reader.get_mut().write_all(format!("{status}\n").as_bytes());
+                }
+            }
+            "" => {}
+            msg => eprintln!("waydo: ignoring unknown command `{}`", msg),
+        }
+        match result {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("waydo: client connection failed: {}", e);
+                break;
+            }
+        }
+    }
+}
+
+const USAGE: &str = "usage: waydo [OPTIONS] [daemon|toggle|show|hide|reload|status|open NAME|check|render NAME OUT|niri-actions]";
+
+const HELP: &str = "\
+Commands:
+  daemon         Run the menu; the other commands talk to it
+  toggle         Show the menu at the pointer, or hide it (default)
+  show           Show the menu unless it already is
+  hide           Hide the menu if it is shown
+  reload         Re-read the config file
+  status         Print whether the menu is shown and which submenu is open
+  open NAME      Show the menu named NAME
+  check          Check the config and every action in it, without GTK
+  render NAME OUT.png
+                 Draw the menu named NAME to a PNG file, without a window
+  niri-actions   List the action names niri accepts, for use in `cmd`
+
+Options:
+  --socket PATH  Control socket (default: $WAYDO_SOCKET or
+                 $XDG_RUNTIME_DIR/waydo.sock)
+  --quiet        Don't report failing actions
+  --verbose      Log debug output (WAYDO_LOG takes env_logger filters)
+  --dry-run      Print the commands actions would run instead of running them
+  --autostart    Start the daemon if toggle, show or open finds none running
+  -h, --help     Print this help
+  -V, --version  Print the version";
+
+fn usage() -> ! {
+    eprintln!("{USAGE}");
+    std::process::exit(2);
 }
 
 fn main() {
-    let arg = env::args().nth(1).unwrap_or_else(|| "toggle".to_string());
+    let mut socket_flag = None;
+    let mut verbose = false;
+    let mut autostart = false;
+    let mut positional = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--socket" {
+            match args.next() {
+                Some(path) => socket_flag = Some(PathBuf::from(path)),
+                None => usage(),
+            }
+        } else if arg == "--quiet" {
+            actions::set_quiet(true);
+        } else if arg == "--dry-run" {
+            actions::set_dry_run(true);
+        } else if arg == "--verbose" {
+            verbose = true;
+        } else if arg == "--autostart" {
+            autostart = true;
+        } else if arg == "--help" || arg == "-h" {
+            println!("{USAGE}\n\n{HELP}");
+            return;
+        } else if arg == "--version" || arg == "-V" {
+            println!("waydo {}", env!("CARGO_PKG_VERSION"));
+            return;
+        } else if let Some(path) = arg.strip_prefix("--socket=") {
+            socket_flag = Some(PathBuf::from(path));
+        } else {
+            positional.push(arg);
+        }
+    }
 
-    match arg.as_str() {
-        "daemon" => run_daemon(),
-        "toggle" => {
-            if let Err(e) = send_toggle() {
+    // Quiet unless asked: --verbose logs at debug level, WAYDO_LOG takes
+    // env_logger filters such as `debug` or `waydo=trace`.
+    let mut logger = env_logger::Builder::from_env(env_logger::Env::new().filter("WAYDO_LOG"));
+    if verbose {
+        logger.filter_level(log::LevelFilter::Debug);
+    }
+    logger.init();
+
+    let socket = socket_path(socket_flag);
+    let args: Vec<&str> = positional.iter().map(String::as_str).collect();
+
+    match args.as_slice() {
+        ["daemon"] => run_daemon(socket),
+        ["check"] => std::process::exit(check_config()),
+        ["niri-actions"] => match actions::niri_actions() {
+            Some(names) => names.iter().for_each(|name| println!("{name}")),
+            None => {
+                eprintln!("waydo: couldn't ask niri for its actions; this list may be out of date");
+                actions::NIRI_ACTIONS
+                    .iter()
+                    .for_each(|name| println!("{name}"));
+            }
+        },
+        ["render", name, out] => {
+            if let Err(e) = render_menu(name, Path::new(out)) {
+                eprintln!("waydo: render failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        [] | ["toggle"] => {
+            if let Err(e) = send_or_start(&socket, "TOGGLE", autostart) {
                 eprintln!("waydo: toggle failed: {}", e);
                 std::process::exit(1);
             }
         }
-        _ => {
-            eprintln!("usage: waydo [daemon|toggle]");
-            std::process::exit(2);
+        ["reload"] => {
+            if let Err(e) = send_command(&socket, "RELOAD") {
+                eprintln!("waydo: reload failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        ["show"] => {
+            if let Err(e) = send_or_start(&socket, "SHOW", autostart) {
+                eprintln!("waydo: show failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        ["hide"] => {
+            if let Err(e) = send_command(&socket, "HIDE") {
+                eprintln!("waydo: hide failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        ["status"] => match query(&socket, "STATUS") {
+            Ok(status) => println!("{status}"),
+            Err(e) => {
+                eprintln!("waydo: status failed: {}", e);
+                std::process::exit(1);
+            }
+        },
+        ["open", name] => {
+            if let Err(e) = send_or_start(&socket, &format!("OPEN {name}"), autostart) {
+                eprintln!("waydo: open failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        _ => usage(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shutdown_removes_the_socket_file() {
+        let socket = env::temp_dir().join(format!("waydo-test-{}.sock", std::process::id()));
+        let _ = fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket).unwrap();
+        assert!(socket.exists());
+
+        shutdown(&socket);
+        assert!(!socket.exists());
+        drop(listener);
+
+        // Nothing to remove the second time round isn't an error.
+        shutdown(&socket);
+    }
+
+    fn assert_near(got: (f64, f64), want: (f64, f64)) {
+        assert!(
+            (got.0 - want.0).abs() < 1e-9 && (got.1 - want.1).abs() < 1e-9,
+            "{got:?} != {want:?}"
+        );
+    }
+
+    // Item 0 sits straight up and the rest follow clockwise on screen,
+    // where y grows downwards.
+    #[test]
+    fn ring_layout_starts_at_the_top_and_runs_clockwise() {
+        let points = ring_layout(4, 0.0, 0.0, 100.0);
+        assert_eq!(points.len(), 4);
+        assert_near(points[0], (0.0, -100.0));
+        assert_near(points[1], (100.0, 0.0));
+        assert_near(points[2], (0.0, 100.0));
+        assert_near(points[3], (-100.0, 0.0));
+
+        assert_near(ring_layout(1, 5.0, 7.0, 10.0)[0], (5.0, -3.0));
+        assert!(ring_layout(0, 0.0, 0.0, 100.0).is_empty());
+    }
+
+    #[test]
+    fn closest_index_for_pointer_respects_both_deadzones() {
+        let points = ring_layout(4, 0.0, 0.0, 100.0);
+        let closest = |x, y| closest_index_for_pointer(x, y, 0.0, 0.0, &points, 20.0, 160.0);
+
+        assert_eq!(closest(0.0, 0.0), None);
+        assert_eq!(closest(10.0, -10.0), None);
+        assert_eq!(closest(0.0, -170.0), None);
+
+        assert_eq!(closest(0.0, -25.0), Some(0));
+        assert_eq!(closest(0.0, -100.0), Some(0));
+        assert_eq!(closest(0.0, -155.0), Some(0));
+        assert_eq!(closest(-100.0, 0.0), Some(3));
+    }
+
+    // On either side of the 45° line between the top and right items.
+    #[test]
+    fn closest_index_for_pointer_picks_the_neighbour_across_a_boundary() {
+        let points = ring_layout(4, 0.0, 0.0, 100.0);
+        let closest = |x, y| closest_index_for_pointer(x, y, 0.0, 0.0, &points, 20.0, 160.0);
+
+        assert_eq!(closest(69.0, -71.0), Some(0));
+        assert_eq!(closest(71.0, -69.0), Some(1));
+        // Same between the last item and the first, where the angle wraps.
+        assert_eq!(closest(-69.0, -71.0), Some(0));
+        assert_eq!(closest(-71.0, -69.0), Some(3));
+    }
+
+    // Runs ticks of commands through the pump's coalescing, each with the
+    // time it arrived at in ms, and returns the number of times the menu
+    // would have been shown or hidden.
+    fn visibility_changes(debounce_ms: u64, ticks: &[(u64, &[&str])]) -> usize {
+        let debounce = Duration::from_millis(debounce_ms);
+        let start = Instant::now();
+        let mut last_toggle = None;
+        let mut visible = false;
+        let mut changes = 0;
+        for &(at, names) in ticks {
+            let cmds = names
+                .iter()
+                .map(|name| match *name {
+                    "toggle" => Command::Toggle,
+                    "show" => Command::Show,
+                    "hide" => Command::Hide,
+                    _ => unreachable!(),
+                })
+                .collect();
+            let now = start + Duration::from_millis(at);
+            for cmd in coalesce_toggles(cmds, &mut last_toggle, now, debounce) {
+                let want = match cmd {
+                    Command::Toggle => !visible,
+                    Command::Show => true,
+                    Command::Hide => false,
+                    _ => visible,
+                };
+                if want != visible {
+                    visible = want;
+                    changes += 1;
+                }
+            }
+        }
+        changes
+    }
+
+    // A keybind bouncing 10ms after the first press must not close the menu
+    // it just opened, whether the bounce lands in the same tick or the next.
+    #[test]
+    fn bouncing_toggles_change_visibility_once() {
+        let debounce = Settings::default().toggle_debounce_ms;
+        assert!(debounce > 10);
+        assert_eq!(
+            visibility_changes(debounce, &[(0, &["toggle"]), (10, &["toggle"])]),
+            1
+        );
+        assert_eq!(
+            visibility_changes(debounce, &[(0, &["toggle", "toggle"])]),
+            1
+        );
+
+        // A deliberate second press later on still closes it.
+        assert_eq!(
+            visibility_changes(debounce, &[(0, &["toggle"]), (300, &["toggle"])]),
+            2
+        );
+        // Without the debounce a burst nets out to its parity.
+        assert_eq!(visibility_changes(0, &[(0, &["toggle", "toggle"])]), 0);
+        assert_eq!(
+            visibility_changes(0, &[(0, &["toggle", "toggle", "toggle"])]),
+            1
+        );
+        // Other commands split a burst rather than being reordered.
+        assert_eq!(
+            visibility_changes(0, &[(0, &["toggle", "hide", "toggle"])]),
+            3
+        );
+        let cmds = vec![Command::Toggle, Command::Toggle, Command::Show];
+        let coalesced = coalesce_toggles(cmds, &mut None, Instant::now(), Duration::ZERO);
+        assert!(matches!(coalesced[..], [Command::Show]), "{coalesced:?}");
+    }
+
+    fn state_for(layout: &str, geometry_scale: f64) -> State {
+        let mut text = format!(
+            "[settings]\nlayout = \"{layout}\"\n[geometry]\nscale = {geometry_scale}\n[menus.root]\n"
+        );
+        for i in 0..7 {
+            text.push_str(&format!(
+                "[[menus.root.items]]\nlabel = \"{i}\"\ncmd = \"x\"\n"
+            ));
+        }
+        let config = config::parse_config(&text).unwrap();
+        let mut st = State {
+            menus: config.menus,
+            active: config::ROOT_MENU.to_string(),
+            settings: config.settings,
+            ..State::default()
+        };
+        refresh_view(&mut st);
+        anchor_at(&mut st, 640.0, 360.0);
+        st
+    }
+
+    // GTK sizes the surface and hands controllers coordinates in logical
+    // pixels, and draws through a context scaled to the output's device
+    // pixels. Every pixel drawn in an item's fill, mapped back the way GTK
+    // maps pointer positions, must hit that item, including on fractional
+    // scales and with the menu pushed back onto the surface.
+    #[test]
+    fn clicks_on_drawn_items_select_them_at_any_scale() {
+        let (w, h) = (640, 480);
+        for layout in ["ring", "wedge", "grid"] {
+            for geometry_scale in [1.0, 1.5] {
+                let mut text = format!(
+                    "[settings]\nlayout = \"{layout}\"\nshadow = false\nkeep_on_screen = true\n\
+                     [geometry]\nscale = {geometry_scale}\n[menus.root]\n"
+                );
+                // Opaque fills told apart by their blue byte.
+                for i in 0..7 {
+                    text.push_str(&format!(
+                        "[[menus.root.items]]\nlabel = \"{i}\"\ncmd = \"x\"\n\
+                         color = [{}, {}, {}]\n",
+                        200.0 / 255.0,
+                        40.0 / 255.0,
+                        (10 + 30 * i) as f64 / 255.0
+                    ));
+                }
+                let config = config::parse_config(&text).unwrap();
+                let mut st = State {
+                    menus: config.menus,
+                    active: config::ROOT_MENU.to_string(),
+                    settings: config.settings,
+                    surface: (w as f64, h as f64),
+                    opacity: 1.0,
+                    ..State::default()
+                };
+                refresh_view(&mut st);
+                anchor_at(&mut st, 20.0, h as f64 - 20.0);
+                assert!(st.cx > 20.0 && st.cy < h as f64 - 20.0, "{layout}");
+
+                for device_scale in [1.0, 1.25, 1.5, 2.0] {
+                    let (dw, dh) = (
+                        (w as f64 * device_scale).ceil() as i32,
+                        (h as f64 * device_scale).ceil() as i32,
+                    );
+                    let mut surface =
+                        gtk::cairo::ImageSurface::create(gtk::cairo::Format::ARgb32, dw, dh)
+                            .unwrap();
+                    surface.set_device_scale(device_scale, device_scale);
+                    draw_ui(&gtk::cairo::Context::new(&surface).unwrap(), w, h, &st);
+                    surface.flush();
+
+                    let stride = surface.stride() as usize;
+                    let data = surface.data().unwrap();
+                    let mut seen = [0; 7];
+                    for py in 0..dh as usize {
+                        for px in 0..dw as usize {
+                            let pixel = &data[py * stride + px * 4..][..4];
+                            let argb = u32::from_ne_bytes(pixel.try_into().unwrap());
+                            let (a, r, g, b) = (
+                                argb >> 24,
+                                (argb >> 16) & 0xff,
+                                (argb >> 8) & 0xff,
+                                argb & 0xff,
+                            );
+                            if (a, r, g) != (0xff, 0xc8, 0x28) || b < 10 || (b - 10) % 30 != 0 {
+                                continue;
+                            }
+                            let i = ((b - 10) / 30) as usize;
+                            seen[i] += 1;
+                            let (x, y) = (
+                                (px as f64 + 0.5) / device_scale,
+                                (py as f64 + 0.5) / device_scale,
+                            );
+                            assert_eq!(
+                                item_at(&st, x, y),
+                                Some(i),
+                                "{layout} at geometry scale {geometry_scale}, device scale \
+                                 {device_scale}: drawn at ({x:.2}, {y:.2})"
+                            );
+                        }
+                    }
+                    assert!(
+                        seen.iter().all(|&n| n > 0),
+                        "{layout} at device scale {device_scale}: {seen:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn clicks_on_disabled_items_select_nothing() {
+        let mut st = state_for("ring", 1.0);
+        st.menus.get_mut(config::ROOT_MENU).unwrap().items[2].enabled = false;
+        refresh_view(&mut st);
+        let points = item_points(&st, st.cx, st.cy);
+
+        let (bx, by) = points[2];
+        assert_eq!(slot_at(&st, bx, by), Some(2));
+        assert_eq!(item_at(&st, bx, by), None);
+        let (bx, by) = points[3];
+        assert_eq!(item_at(&st, bx, by), Some(3));
+
+        assert_eq!(next_selectable(st.items(), 1, 1), Some(3));
+    }
+
+    #[test]
+    fn stale_paths_are_cut_back_to_the_deepest_valid_level() {
+        let text = "[menus.root]\n\
+                    [[menus.root.items]]\nlabel = \"a\"\nsubmenu = \"mid\"\n\
+                    [[menus.root.items]]\nlabel = \"x\"\ncmd = \"x\"\n\
+                    [menus.mid]\n\
+                    [[menus.mid.items]]\nlabel = \"b\"\nsubmenu = \"leaf\"\n\
+                    [menus.leaf]\n\
+                    [[menus.leaf.items]]\nlabel = \"c\"\ncmd = \"c\"\n";
+        let config = config::parse_config(text).unwrap();
+        let mut st = State {
+            menus: config.menus,
+            active: config::ROOT_MENU.to_string(),
+            settings: config.settings,
+            ..State::default()
+        };
+        refresh_view(&mut st);
+
+        let root = st.menu();
+        assert_eq!(valid_depth(root, &[0, 0]), 2);
+        assert_eq!(current_menu(root, &[0, 0]).name, "leaf");
+        // Past a leaf, out of range, or through an action item.
+        assert_eq!(valid_depth(root, &[0, 0, 0]), 2);
+        assert_eq!(valid_depth(root, &[0, 5, 0]), 1);
+        assert_eq!(current_menu(root, &[0, 5, 0]).name, "mid");
+        assert_eq!(valid_depth(root, &[1, 0]), 0);
+        assert_eq!(current_menu(root, &[1, 0]).name, "root");
+
+        st.path = vec![0, 0, 3, 1];
+        st.highlight = Some(4);
+        refresh_view(&mut st);
+        assert_eq!(st.path, [0, 0]);
+        assert_eq!(st.items()[0].label, "c");
+        assert_eq!(st.highlight, None);
+
+        // A reload that drops the submenu leaves the root open.
+        st.menus.get_mut(config::ROOT_MENU).unwrap().items.remove(0);
+        refresh_view(&mut st);
+        assert!(st.path.is_empty());
+        assert_eq!(st.items()[0].label, "x");
+    }
+
+    #[test]
+    fn an_unallocated_surface_leaves_the_layout_finite() {
+        for layout in ["ring", "wedge", "grid"] {
+            let mut st = state_for(layout, 1.0);
+            st.settings.keep_on_screen = true;
+            st.surface = (0.0, 0.0);
+            anchor_at(&mut st, 0.0, 0.0);
+            assert_eq!((st.cx, st.cy), (0.0, 0.0), "{layout}");
+            for (x, y) in item_points(&st, st.cx, st.cy) {
+                assert!(x.is_finite() && y.is_finite(), "{layout}: ({x}, {y})");
+            }
+            let (x0, y0, x1, y1) = menu_extent(&st);
+            assert!([x0, y0, x1, y1].iter().all(|v| v.is_finite()), "{layout}");
+        }
+        let extent = (-50.0, -50.0, 50.0, 50.0);
+        assert_eq!(fit_on_surface(10.0, 20.0, extent, (0.0, 0.0)), (10.0, 20.0));
+        assert_eq!(
+            fit_on_surface(10.0, 20.0, extent, (f64::NAN, 0.0)),
+            (10.0, 20.0)
+        );
+        assert_eq!(
+            fit_on_surface(10.0, 20.0, extent, (400.0, 300.0)),
+            (50.0, 50.0)
+        );
+    }
+
+    // The menu is nearly always anchored by motion, the grace timer or
+    // `spawn` before a button goes down, so the flick has to start from the
+    // center of an anchored menu as well as from the press that anchors it.
+    #[test]
+    fn release_drag_flicks_from_the_center_to_an_item() {
+        const PRIMARY: u32 = gdk::BUTTON_PRIMARY;
+        let mut st = state_for("ring", 1.0);
+        st.visible = true;
+        st.settings.selection = Selection::ReleaseDrag;
+        let (cx, cy) = (st.cx, st.cy);
+        let points = item_points(&st, cx, cy);
+
+        assert!(!pressed_at(&mut st, PRIMARY, cx, cy));
+        assert_eq!(
+            pointer_moved(&mut st, points[2].0, points[2].1),
+            Moved::Redraw
+        );
+        assert_eq!(
+            released_at(&mut st, PRIMARY, points[2].0, points[2].1),
+            Release::Activate(2)
+        );
+
+        // Dragged back to the center, it was a click there after all.
+        pressed_at(&mut st, PRIMARY, cx, cy);
+        assert_eq!(pointer_moved(&mut st, cx, cy), Moved::Redraw);
+        assert_eq!(released_at(&mut st, PRIMARY, cx, cy), Release::Select);
+
+        // Pressed away from the center, it's a click wherever it ends.
+        pressed_at(&mut st, PRIMARY, points[4].0, points[4].1);
+        pointer_moved(&mut st, points[5].0, points[5].1);
+        assert_eq!(
+            released_at(&mut st, PRIMARY, points[5].0, points[5].1),
+            Release::Select
+        );
+
+        // A press that anchors the menu starts a flick too, and let go
+        // without leaving the center the menu stays open.
+        pointer_moved(&mut st, cx, cy);
+        st.anchored = false;
+        assert!(pressed_at(&mut st, PRIMARY, 300.0, 200.0));
+        assert!(st.anchored);
+        let (ax, ay) = (st.cx, st.cy);
+        assert_eq!(pointer_moved(&mut st, ax, ay), Moved::Nothing);
+        assert_eq!(released_at(&mut st, PRIMARY, ax, ay), Release::Nothing);
+
+        st.anchored = false;
+        pressed_at(&mut st, PRIMARY, 300.0, 200.0);
+        let (bx, by) = item_points(&st, ax, ay)[0];
+        assert_eq!(pointer_moved(&mut st, bx, by), Moved::Redraw);
+        assert_eq!(released_at(&mut st, PRIMARY, bx, by), Release::Activate(0));
+
+        // Plain clicks never flick.
+        st.settings.selection = Selection::Click;
+        pointer_moved(&mut st, ax, ay);
+        pressed_at(&mut st, PRIMARY, ax, ay);
+        pointer_moved(&mut st, bx, by);
+        assert_eq!(released_at(&mut st, PRIMARY, bx, by), Release::Select);
+    }
+
+    #[test]
+    fn center_click_selects_nothing() {
+        for layout in ["ring", "wedge", "grid"] {
+            let st = state_for(layout, 1.5);
+            assert_eq!(item_at(&st, st.cx, st.cy), None, "{layout}");
         }
     }
 }
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+// Items need this many uses before `frequent` moves them, so one stray click
+// doesn't reshuffle a menu.
+const MIN_USES: u64 = 3;
+
+// How often each item was activated, keyed by `key`, and kept in
+// `$XDG_STATE_HOME/waydo/usage` as `count<TAB>key` lines.
+#[derive(Debug, Default)]
+pub struct Usage {
+    counts: HashMap<String, u64>,
+}
+
+pub fn usage_path() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_STATE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?)
+            .join(".local")
+            .join("state"),
+    };
+    Some(base.join("waydo").join("usage"))
+}
+
+// Identifies an item by the menu it is in and the labels leading to it, so
+// counts survive reordering and edits elsewhere in the config.
+pub fn key(menu: &str, path: &[&str], label: &str) -> String {
+    let mut key = menu.to_string();
+    for part in path.iter().chain([&label]) {
+        key.push('/');
+        key.push_str(part);
+    }
+    key
+}
+
+impl Usage {
+    // A missing or unreadable file just starts the counts afresh.
+    pub fn load() -> Self {
+        let Some(text) = usage_path().and_then(|path| fs::read_to_string(path).ok()) else {
+            return Self::default();
+        };
+        let counts = text
+            .lines()
+            .filter_map(|line| {
+                let (count, key) = line.split_once('\t')?;
+                Some((key.to_string(), count.parse().ok()?))
+            })
+            .collect();
+        Self { counts }
+    }
+
+    pub fn count(&self, key: &str) -> u64 {
+        self.counts.get(key).copied().unwrap_or(0)
+    }
+
+    pub fn bump(&mut self, key: String) {
+        *self.counts.entry(key).or_default() += 1;
+        if let Err(e) = self.save() {
+            eprintln!("waydo: failed to save usage counts: {}", e);
+        }
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let path = usage_path().ok_or("no state directory")?;
+        let mut keys: Vec<_> = self.counts.keys().collect();
+        keys.sort();
+        let text: String = keys
+            .into_iter()
+            .map(|key| format!("{}\t{}\n", self.counts[key], key))
+            .collect();
+        let tmp = path.with_extension("tmp");
+        path.parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::write(&tmp, text))
+            .and_then(|()| fs::rename(&tmp, &path))
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+// Order for a menu whose items were used `counts` times: up to `max` of the
+// most used come first, the rest follow. Both groups keep the items' config
+// order, so a promoted item's place only changes when it joins or leaves the
+// frequent group.
+pub fn frequent_first(counts: &[u64], max: usize) -> Vec<usize> {
+    let mut used: Vec<usize> = (0..counts.len())
+        .filter(|&i| counts[i] >= MIN_USES)
+        .collect();
+    used.sort_by_key(|&i| std::cmp::Reverse(counts[i]));
+    used.truncate(max);
+    used.sort();
+    let rest = (0..counts.len()).filter(|i| !used.contains(i));
+    used.iter().copied().chain(rest).collect()
+}